@@ -1,7 +1,7 @@
-use ansi_term::Style;
 use std::io;
 use termion::terminal_size;
 use termpixels::app;
+use termpixels::style::Style;
 use termpixels::types::*;
 
 struct MyCanvas {}
@@ -17,34 +17,12 @@ impl Canvas for MyCanvas {
     }
 }
 
-fn init() -> io::Result<(MyCanvas, MyModel)> {
+fn init(_: &Context<()>) -> io::Result<(MyCanvas, MyModel)> {
     Ok((MyCanvas {}, MyModel {}))
 }
 
-pub fn view<C: Canvas, M: Model>(
-    canvas: &C,
-    _m: &M,
-    position: &Position,
-) -> io::Result<Option<TermPixel>> {
-    if position == &canvas.top_left_corner()? {
-        Ok(Some(('┌', Style::default())))
-    } else if position == &canvas.top_right_corner()? {
-        Ok(Some(('┐', Style::default())))
-    } else if position == &canvas.bottom_left_corner()? {
-        Ok(Some(('└', Style::default())))
-    } else if position == &canvas.bottom_right_corner()? {
-        Ok(Some(('┘', Style::default())))
-    } else if position == &canvas.right_boundary(position.1)?
-        || position == &canvas.left_boundary(position.1)?
-    {
-        Ok(Some(('│', Style::default())))
-    } else if position == &canvas.top_boundary(position.0)?
-        || position == &canvas.bottom_boundary(position.0)?
-    {
-        Ok(Some(('─', Style::default())))
-    } else {
-        Ok(None)
-    }
+fn view(canvas: &MyCanvas, _: &MyModel, buffer: &mut Buffer) -> io::Result<()> {
+    buffer.set_border(canvas, Style::default())
 }
 
 fn update(_: &MyCanvas, _: &mut MyModel, _event: &Event<()>) -> io::Result<Event<()>> {
@@ -52,5 +30,5 @@ fn update(_: &MyCanvas, _: &mut MyModel, _event: &Event<()>) -> io::Result<Event
 }
 
 fn main() {
-    app::run(&init, &view, &update, None).unwrap();
+    app::run_and_exit(&init, &view, &update, &app::Options::default()).unwrap();
 }
@@ -1,12 +1,11 @@
-use ansi_term::{Color, Style};
 use std::io;
 use termion::terminal_size;
 use termpixels::app;
-use termpixels::event::{Event, Input, Key, Mouse};
+use termpixels::event::{Event, Input, KeyCode, Mouse};
+use termpixels::style::{Color, Style};
 use termpixels::types::*;
 
 struct MyCanvas {
-    size: Size,
     bg_style: Style,
 }
 
@@ -15,7 +14,7 @@ impl Canvas for MyCanvas {
         Ok((1, 1))
     }
     fn bottom_right_corner(&self) -> io::Result<Position> {
-        Ok(self.size)
+        terminal_size()
     }
 }
 
@@ -40,10 +39,9 @@ struct MyModel {
     input_box: MyInputBox,
 }
 
-fn init() -> io::Result<(MyCanvas, MyModel)> {
+fn init(_: &Context<()>) -> io::Result<(MyCanvas, MyModel)> {
     let cv = MyCanvas {
         bg_style: Style::default(),
-        size: terminal_size()?,
     };
     let model = MyModel {
         input_box: MyInputBox {
@@ -59,40 +57,37 @@ fn init() -> io::Result<(MyCanvas, MyModel)> {
 fn update(_: &MyCanvas, model: &mut MyModel, event: &Event<()>) -> io::Result<Event<()>> {
     match event {
         Event::GracefulStop => Ok(Event::Stop),
-        Event::Input(Input::Key(k)) => match k {
-            Key::Char(c) => {
-                model.input_box.value = *c;
+        Event::Input(Input::Key(k)) => match k.code {
+            KeyCode::Char(c) => {
+                model.input_box.value = c;
                 Ok(Event::NoOp)
             }
-            Key::Up => {
+            KeyCode::Up => {
                 let (x, y) = model.input_box.center()?;
                 model.input_box.center = (x, y - 1);
                 Ok(Event::NoOp)
             }
-            Key::Down => {
+            KeyCode::Down => {
                 let (x, y) = model.input_box.center()?;
                 model.input_box.center = (x, y + 1);
                 Ok(Event::NoOp)
             }
-            Key::Left => {
+            KeyCode::Left => {
                 let (x, y) = model.input_box.center()?;
                 model.input_box.center = (x - 1, y);
                 Ok(Event::NoOp)
             }
-            Key::Right => {
+            KeyCode::Right => {
                 let (x, y) = model.input_box.center()?;
                 model.input_box.center = (x + 1, y);
                 Ok(Event::NoOp)
             }
             _ => Ok(Event::NoOp),
         },
-        Event::Input(Input::Mouse(m)) => match m {
-            Mouse::Press(_, x, y) | Mouse::Hold(x, y) => {
-                model.input_box.center = (*x, *y);
-                Ok(Event::NoOp)
-            }
-            _ => Ok(Event::NoOp),
-        },
+        Event::Input(Input::Mouse(Mouse::Press(_, x, y) | Mouse::Hold(x, y))) => {
+            model.input_box.center = (*x, *y);
+            Ok(Event::NoOp)
+        }
         _ => Ok(Event::NoOp),
     }
 }
@@ -108,5 +103,5 @@ fn view(canvas: &MyCanvas, model: &MyModel, position: &Position) -> io::Result<O
 }
 
 fn main() {
-    app::run(&init, &view, &update, None).unwrap();
+    app::run_and_exit(&init, &view, &update, &app::Options::default()).unwrap();
 }
@@ -0,0 +1,94 @@
+//! Picks one of the lines read from stdin and prints it to stdout, e.g.
+//! `ls | cargo run --example picker | xargs echo picked`.
+use std::io;
+use std::io::prelude::*;
+use termpixels::app;
+use termpixels::event::{Event, Input, KeyCode, KeyEvent};
+use termpixels::style::Style;
+use termpixels::types::*;
+
+const LINES: u16 = 5;
+
+struct MyCanvas {}
+
+impl Canvas for MyCanvas {
+    fn top_left_corner(&self) -> io::Result<Position> {
+        Ok((1, 1))
+    }
+    fn bottom_right_corner(&self) -> io::Result<Position> {
+        Ok((80, LINES))
+    }
+}
+
+struct MyModel {
+    items: Vec<String>,
+    selected: usize,
+    picked: bool,
+}
+
+fn view(_: &MyCanvas, model: &MyModel, buffer: &mut Buffer) -> io::Result<()> {
+    let first = model.selected.saturating_sub(LINES as usize - 1);
+    for (i, item) in model
+        .items
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(LINES as usize)
+    {
+        let y = (i - first) as u16 + 1;
+        match i == model.selected {
+            true => buffer.set_string(&(1, y), &format!("> {}", item), Style::default().reverse()),
+            false => buffer.set_string(&(1, y), &format!("  {}", item), Style::default()),
+        };
+    }
+    Ok(())
+}
+
+fn update(_: &MyCanvas, model: &mut MyModel, event: &Event<()>) -> io::Result<Event<()>> {
+    match event {
+        Event::Input(Input::Key(KeyEvent {
+            code: KeyCode::Up, ..
+        })) => model.selected = model.selected.saturating_sub(1),
+        Event::Input(Input::Key(KeyEvent {
+            code: KeyCode::Down,
+            ..
+        })) if model.selected + 1 < model.items.len() => model.selected += 1,
+        Event::Input(Input::Key(KeyEvent {
+            code: KeyCode::Enter,
+            ..
+        })) => {
+            model.picked = true;
+            return Ok(Event::Stop);
+        }
+        Event::Input(Input::Key(KeyEvent {
+            code: KeyCode::Esc, ..
+        }))
+        | Event::GracefulStop => return Ok(Event::Stop),
+        _ => {}
+    }
+    Ok(Event::NoOp)
+}
+
+fn main() -> io::Result<()> {
+    let items = io::stdin().lock().lines().collect::<io::Result<Vec<_>>>()?;
+    let init = move |_: &Context<()>| {
+        let model = MyModel {
+            items: items.clone(),
+            selected: 0,
+            picked: false,
+        };
+        Ok((MyCanvas {}, model))
+    };
+    let options = app::Options {
+        viewport: app::Viewport::Inline(LINES),
+        ..Default::default()
+    };
+
+    let exit = app::run(&init, &view, &update, &options)?;
+    if exit.model.picked {
+        if let Some(item) = exit.model.items.get(exit.model.selected) {
+            println!("{}", item);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::io;
+use std::time::Duration;
+use termpixels::app;
+use termpixels::style::{Color, Style};
+use termpixels::types::*;
+
+struct MyCanvas {}
+
+impl Canvas for MyCanvas {
+    fn top_left_corner(&self) -> io::Result<Position> {
+        Ok((1, 1))
+    }
+    fn bottom_right_corner(&self) -> io::Result<Position> {
+        Ok((WIDTH + 2, 2))
+    }
+}
+
+const WIDTH: u16 = 40;
+
+fn init(_: &Context<()>) -> io::Result<(MyCanvas, u16)> {
+    Ok((MyCanvas {}, 0))
+}
+
+fn view(_: &MyCanvas, done: &u16, buffer: &mut Buffer) -> io::Result<()> {
+    buffer.set_string(
+        &(1, 1),
+        &format!("Working... {}%", done * 100 / WIDTH),
+        Style::default(),
+    );
+    let bar = "█".repeat(*done as usize) + &"░".repeat((WIDTH - done) as usize);
+    buffer.set_string(&(1, 2), &bar, Color::Green.normal());
+    Ok(())
+}
+
+fn update(_: &MyCanvas, done: &mut u16, event: &Event<()>) -> io::Result<Event<()>> {
+    match event {
+        Event::Tick { .. } if *done < WIDTH => {
+            *done += 1;
+            Ok(Event::NoOp)
+        }
+        Event::Tick { .. } | Event::GracefulStop => Ok(Event::Stop),
+        _ => Ok(Event::NoOp),
+    }
+}
+
+fn main() {
+    let options = app::Options {
+        tick_rate: Some(Duration::from_millis(50)),
+        viewport: app::Viewport::Inline(2),
+        ..Default::default()
+    };
+    app::run_and_exit(&init, &view, &update, &options).unwrap();
+}
@@ -1,11 +1,11 @@
-use ansi_term::{Color, Style};
 use std::collections::VecDeque;
 use std::io;
 use std::time::Duration;
 use std::time::SystemTime;
 use termion::terminal_size;
 use termpixels::app;
-use termpixels::event::{Event, Input, Key};
+use termpixels::event::{Event, Input, KeyCode};
+use termpixels::style::{Color, Style};
 use termpixels::types::*;
 
 struct MyCanvas {
@@ -84,8 +84,8 @@ struct Food {
 
 impl Food {
     fn new(canvas: &MyCanvas) -> io::Result<Food> {
-        let mut bad_rand1 = u16::max_value();
-        let mut bad_rand2 = u16::max_value();
+        let mut bad_rand1 = u16::MAX;
+        let mut bad_rand2 = u16::MAX;
 
         let (w, h) = canvas.size()?;
 
@@ -113,41 +113,65 @@ impl Food {
 }
 
 struct MyModel {
-    fast: bool,
     snake: Snake,
     food: Food,
+    game_over: bool,
 }
 
-fn init() -> io::Result<(MyCanvas, MyModel)> {
+fn init(_: &Context<Msg>) -> io::Result<(MyCanvas, MyModel)> {
     let cv = MyCanvas {
         size: terminal_size()?,
         fill_ascii: ' ',
         fill_style: Style::default().on(Color::Black),
     };
     let model = MyModel {
-        fast: false,
         snake: Snake::new(&cv)?,
         food: Food::new(&cv)?,
+        game_over: false,
     };
     Ok((cv, model))
 }
 
+fn step(canvas: &MyCanvas, model: &mut MyModel) -> io::Result<Event<Msg>> {
+    let &(x, y) = model.snake.body.front().unwrap();
+    let next = match model.snake.direction {
+        Direction::Up => (x, y - 1),
+        Direction::Down => (x, y + 1),
+        Direction::Right => (x + 1, y),
+        Direction::Left => (x - 1, y),
+    };
+
+    if canvas.is_boundary(&next)? || model.snake.body.contains(&next) {
+        Ok(Event::Msg(Msg::GameOver))
+    } else if next == model.food.position {
+        model.snake.body.push_front(next);
+        Ok(Event::Msg(Msg::FoodEaten))
+    } else {
+        model.snake.body.push_front(next);
+        model.snake.body.pop_back();
+        Ok(Event::NoOp)
+    }
+}
+
 fn update(canvas: &MyCanvas, model: &mut MyModel, event: &Event<Msg>) -> io::Result<Event<Msg>> {
     match event {
         Event::GracefulStop => Ok(Event::Stop),
-        Event::Input(Input::Key(k)) => match k {
-            Key::Up | Key::Char('k') => Ok(Event::Msg(Msg::ChangeDirection(Direction::Up))),
-            Key::Down | Key::Char('j') => Ok(Event::Msg(Msg::ChangeDirection(Direction::Down))),
-            Key::Left | Key::Char('h') => Ok(Event::Msg(Msg::ChangeDirection(Direction::Left))),
-            Key::Right | Key::Char('l') => Ok(Event::Msg(Msg::ChangeDirection(Direction::Right))),
+        Event::Input(Input::Key(k)) => match k.code {
+            KeyCode::Up | KeyCode::Char('k') => Ok(Event::Msg(Msg::ChangeDirection(Direction::Up))),
+            KeyCode::Down | KeyCode::Char('j') => {
+                Ok(Event::Msg(Msg::ChangeDirection(Direction::Down)))
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                Ok(Event::Msg(Msg::ChangeDirection(Direction::Left)))
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                Ok(Event::Msg(Msg::ChangeDirection(Direction::Right)))
+            }
             _ => Ok(Event::NoOp),
         },
         Event::Msg(msg) => match msg {
             Msg::ChangeDirection(direction) => match &model.snake.direction == direction {
-                true => {
-                    model.fast = true;
-                    Ok(Event::NoOp)
-                }
+                true => step(canvas, model),
                 _ => match direction == &model.snake.direction.opposite() {
                     true => Ok(Event::NoOp),
                     _ => {
@@ -161,37 +185,11 @@ fn update(canvas: &MyCanvas, model: &mut MyModel, event: &Event<Msg>) -> io::Res
                 Ok(Event::NoOp)
             }
             Msg::GameOver => {
-                println!("\nGame Over!");
+                model.game_over = true;
                 Ok(Event::Stop)
             }
         },
-        Event::NoOp => {
-            let &(x, y) = model.snake.body.front().unwrap();
-            {
-                let next = match model.snake.direction {
-                    Direction::Up => (x + 0, y - 1),
-                    Direction::Down => (x + 0, y + 1),
-                    Direction::Right => (x + 1, y + 0),
-                    Direction::Left => (x - 1, y + 0),
-                };
-
-                if canvas.is_boundary(&next)? || model.snake.body.contains(&next) {
-                    Ok(Event::Msg(Msg::GameOver))
-                } else if next == model.food.position {
-                    model.snake.body.push_front(next);
-                    Ok(Event::Msg(Msg::FoodEaten))
-                } else {
-                    model.snake.body.push_front(next);
-                    model.snake.body.pop_back();
-                    if !model.fast {
-                        std::thread::sleep(Duration::from_millis(150));
-                    } else {
-                        model.fast = false
-                    }
-                    Ok(Event::NoOp)
-                }
-            }
-        }
+        Event::Tick { .. } => step(canvas, model),
         _ => Ok(Event::NoOp),
     }
 }
@@ -239,5 +237,13 @@ fn view(canvas: &MyCanvas, model: &MyModel, position: &Position) -> io::Result<O
 }
 
 fn main() {
-    app::run(&init, &view, &update, None).unwrap();
+    let options = app::Options {
+        tick_rate: Some(Duration::from_millis(150)),
+        ..Default::default()
+    };
+    let exit = app::run(&init, &view, &update, &options).unwrap();
+    if exit.model.game_over {
+        println!("Game Over! Score: {}", exit.model.snake.body.len() - 3);
+    }
+    std::process::exit(exit.code as i32);
 }
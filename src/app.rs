@@ -1,125 +1,1249 @@
+use crate::backend::{Backend, TermionBackend};
+use crate::buffer::{Buffer, Cell};
 use crate::canvas::Canvas;
-use crate::event::{Event, Input, Key};
+use crate::cursor::Cursor;
+use crate::event::{Event, Input, KeyCode, KeyEvent, Modifiers, TerminalEvent};
 use crate::exit_code::ExitCode;
+use crate::interrupt::{Interrupt, Interrupter, Request};
+use crate::scheduler::Scheduler;
+use crate::suspend::Suspender;
+use crate::sys;
 use crate::types::*;
-use std::collections::HashMap;
+use signal_hook::consts::SIGTSTP;
 use std::io;
-use std::io::prelude::*;
-use std::time::Duration;
-use termion::async_stdin;
-use termion::input::MouseTerminal;
-use termion::input::TermRead;
-use termion::raw::IntoRawMode;
+use std::ops::{Deref, DerefMut};
+use std::panic;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+type PanicHook = Box<dyn Fn(&panic::PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+/// Owns the terminal state while the app is running and restores it when dropped, so that
+/// early returns and errors leave the user with a usable shell.
+struct Session<'a, B: Backend> {
+    backend: &'a mut B,
+    viewport: Viewport,
+    keyboard: Keyboard,
+    focus_events: bool,
+    /// The screen row an inline viewport starts at.
+    origin: u16,
+    active: bool,
+    restored: Arc<AtomicBool>,
+    previous_hook: Option<Arc<PanicHook>>,
+}
+
+impl<'a, B: Backend> Session<'a, B> {
+    fn start(backend: &'a mut B, options: &Options) -> io::Result<Self> {
+        let mut session = Self {
+            backend,
+            viewport: options.viewport,
+            keyboard: options.keyboard,
+            focus_events: options.focus_events,
+            origin: 1,
+            active: true,
+            restored: Arc::new(AtomicBool::new(false)),
+            previous_hook: None,
+        };
+        session.enter()?;
+        session.install_panic_hook();
+        Ok(session)
+    }
+
+    fn enter(&mut self) -> io::Result<()> {
+        self.enter_raw_mode()?;
+        match self.viewport {
+            Viewport::Fullscreen => {
+                self.enter_alternate_screen()?;
+                self.enable_mouse()?;
+            }
+            Viewport::Inline(_) => self.reserve()?,
+        }
+        self.enable_bracketed_paste()?;
+        match self.keyboard {
+            Keyboard::Legacy => {}
+            Keyboard::Enhanced => self.enable_keyboard_enhancement(false)?,
+            Keyboard::EnhancedWithReleases => self.enable_keyboard_enhancement(true)?,
+        }
+        if self.focus_events {
+            self.enable_focus_events()?;
+        }
+        self.hide_cursor()?;
+        self.flush()
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        let cursor = self.reset_cursor_shape().and(self.show_cursor());
+        let paste = self.disable_bracketed_paste();
+        let keyboard = match self.keyboard {
+            Keyboard::Legacy => Ok(()),
+            _ => self.disable_keyboard_enhancement(),
+        };
+        let focus = match self.focus_events {
+            true => self.disable_focus_events(),
+            false => Ok(()),
+        };
+        let screen = match self.viewport {
+            Viewport::Fullscreen => self.disable_mouse().and(self.leave_alternate_screen()),
+            Viewport::Inline(_) => self.release(),
+        };
+        let flush = self.flush();
+        let raw = self.leave_raw_mode();
+        cursor
+            .and(paste)
+            .and(keyboard)
+            .and(focus)
+            .and(screen)
+            .and(flush)
+            .and(raw)
+    }
+
+    /// Makes room for an inline viewport from the line of the cursor down, scrolling the
+    /// screen up if there is not enough of it left.
+    fn reserve(&mut self) -> io::Result<()> {
+        let (_, rows) = self.size()?;
+        let height = self.height(rows);
+        let row = match self.cursor_position()? {
+            Some((1, y)) => y,
+            Some((_, y)) => y + 1,
+            None => rows,
+        };
+        let overflow = (row + height - 1).saturating_sub(rows);
+        self.scroll_up(overflow)?;
+        self.origin = (row - overflow).max(1);
+        Ok(())
+    }
+
+    /// Moves the cursor below an inline viewport, leaving the last frame for the shell to
+    /// scroll away.
+    fn release(&mut self) -> io::Result<()> {
+        let (_, rows) = self.size()?;
+        let below = self.origin + self.height(rows);
+        if below > rows {
+            self.scroll_up(1)
+        } else {
+            self.goto((1, below))
+        }
+    }
+
+    /// The number of rows the frames take up on a screen of `rows`.
+    fn height(&self, rows: u16) -> u16 {
+        match self.viewport {
+            Viewport::Fullscreen => rows,
+            Viewport::Inline(lines) => lines.clamp(1, rows.max(1)),
+        }
+    }
+
+    /// The size of the frames drawn on a screen of `size`.
+    fn frame_size(&self, size: Size) -> Size {
+        (size.0, self.height(size.1))
+    }
+
+    /// Moves an inline viewport up if the screen got too short for it.
+    fn fit(&mut self, size: Size) {
+        let (_, rows) = size;
+        self.origin = self.origin.min(rows.saturating_sub(self.height(rows)) + 1);
+    }
+
+    /// The screen row of the top of an inline viewport.
+    fn origin(&self) -> Option<u16> {
+        match self.viewport {
+            Viewport::Fullscreen => None,
+            Viewport::Inline(_) => Some(self.origin),
+        }
+    }
+
+    /// Hands the terminal back to the shell while the process is stopped.
+    fn suspend(&mut self) -> io::Result<()> {
+        self.leave()?;
+        self.backend.suspend()?;
+        self.enter()
+    }
+
+    /// Takes the terminal back after the process was stopped and continued by someone else,
+    /// whose shell may have reset it in the meantime.
+    fn resume(&mut self) -> io::Result<()> {
+        self.leave_raw_mode()?;
+        self.enter()
+    }
+
+    /// Lets `command` use the terminal until it exits. A Ctrl-Z meant for it stops us as well,
+    /// as we would otherwise keep waiting for a process that is stopped.
+    fn exec(&mut self, command: &mut Command) -> io::Result<io::Result<ExitStatus>> {
+        self.attach(command)?;
+        self.leave()?;
+        let status = sys::with_default_action(SIGTSTP, || command.status())?;
+        self.enter()?;
+        Ok(status)
+    }
+
+    /// Chains a panic hook that restores the terminal before the previous hook prints the
+    /// panic, which would otherwise end up garbled on the raw alternate screen. Only panics on
+    /// the thread running the app do so; the app keeps drawing through panics elsewhere.
+    fn install_panic_hook(&mut self) {
+        let restore = self.restorer();
+        let restored = self.restored.clone();
+        let owner = thread::current().id();
+        let previous = Arc::new(panic::take_hook());
+        let chained = previous.clone();
+
+        panic::set_hook(Box::new(move |info| {
+            if thread::current().id() == owner && !restored.swap(true, Ordering::SeqCst) {
+                restore();
+            }
+            chained(info);
+        }));
+        self.previous_hook = Some(previous);
+    }
+
+    fn uninstall_panic_hook(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+
+        if let Some(previous) = self.previous_hook.take() {
+            let _ = panic::take_hook();
+            match Arc::try_unwrap(previous) {
+                Ok(previous) => panic::set_hook(previous),
+                Err(previous) => panic::set_hook(Box::new(move |info| previous(info))),
+            }
+        }
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        self.active = false;
+        self.restored.store(true, Ordering::SeqCst);
+        self.uninstall_panic_hook();
+        self.leave()
+    }
+}
+
+impl<B: Backend> Deref for Session<'_, B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        self.backend
+    }
+}
+
+impl<B: Backend> DerefMut for Session<'_, B> {
+    fn deref_mut(&mut self) -> &mut B {
+        self.backend
+    }
+}
+
+impl<B: Backend> Drop for Session<'_, B> {
+    fn drop(&mut self) {
+        let _ = self.end();
+    }
+}
+
+/// Draws the canvas into `buffer`. Positions for which the view has nothing to show are left
+/// untouched, and so is the position right after a wide character.
 pub fn render<C: Canvas, M: Model, V: View<C, M>>(
     canvas: &C,
     model: &M,
     view: &V,
-) -> io::Result<Vec<(Position, TermPixel)>> {
-    let mut vec: Vec<(Position, TermPixel)> = Vec::new();
-
+    buffer: &mut Buffer,
+) -> io::Result<()> {
     let (x1, y1) = canvas.top_left_corner()?;
     let (x2, y2) = canvas.bottom_right_corner()?;
 
     for y in y1..(y2 + 1) {
-        for x in x1..(x2 + 1) {
+        let mut x = x1;
+        while x <= x2 {
+            let mut width = 1;
             if let Ok(Some(tp)) = view(canvas, model, &(x, y)) {
-                vec.push(((x, y), tp));
+                let cell = Cell::from(tp);
+                width = cell.width();
+                buffer.set(&(x, y), cell);
+            }
+            x = match x.checked_add(width) {
+                Some(x) => x,
+                None => break,
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Writes the pixels of `frame` that differ from what is already on the screen, as a single
+/// frame, and then puts the cursor where the frame asks for it. If the screen is `stale`, it
+/// is cleared and fully redrawn. An `origin` moves everything down to an inline viewport
+/// starting at that row.
+fn paint<B: Backend>(
+    backend: &mut B,
+    screen: &mut Buffer,
+    frame: &Buffer,
+    stale: bool,
+    origin: Option<u16>,
+) -> io::Result<()> {
+    if stale {
+        screen.clear();
+    }
+
+    let offset = origin.map_or(0, |row| row - 1);
+    let place = |(x, y): Position| (x, y + offset);
+    let changes: Vec<_> = screen
+        .diff(frame)
+        .into_iter()
+        .map(|(position, cell)| (place(position), cell))
+        .collect();
+    if changes.is_empty() && !stale && screen.cursor() == frame.cursor() {
+        return Ok(());
+    }
+
+    backend.begin_frame()?;
+    if stale {
+        match origin {
+            Some(row) => backend.clear_from(row)?,
+            None => backend.clear()?,
+        }
+    }
+    backend.draw(&changes)?;
+    match (screen.cursor(), frame.cursor()) {
+        (previous, Some(cursor)) => {
+            backend.goto(place(cursor.position))?;
+            let shape = |c: Cursor| (c.shape, c.blinking);
+            if previous.map(shape) != Some(shape(cursor)) {
+                backend.set_cursor_shape(cursor.shape, cursor.blinking)?;
+            }
+            if previous.is_none() {
+                backend.show_cursor()?;
             }
         }
+        (Some(_), None) => backend.hide_cursor()?,
+        (None, None) if stale => backend.hide_cursor()?,
+        (None, None) => {}
+    }
+    backend.end_frame()?;
+    screen.clone_from(frame);
+    backend.flush()
+}
+
+/// How an app ended, along with its final model.
+pub struct Exit<M> {
+    pub model: M,
+    pub code: ExitCode,
+}
+
+/// Where on the terminal the app draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewport {
+    /// The whole alternate screen, with mouse reporting on.
+    Fullscreen,
+    /// This many lines from the cursor down, scrolling the terminal up if needed. Canvas
+    /// positions are relative to the first of them, and the last frame is left behind in the
+    /// scrollback. The mouse is left alone, so that the terminal can still scroll and select.
+    Inline(u16),
+}
+
+/// How keys are read from the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyboard {
+    /// The legacy xterm sequences, which every terminal sends.
+    Legacy,
+    /// The kitty keyboard protocol where the terminal supports it, which tells apart keys the
+    /// legacy sequences can't, such as Tab and Ctrl-I or Esc and Alt, and reports every
+    /// modifier.
+    Enhanced,
+    /// Like `Enhanced`, also reporting repeats and releases.
+    EnhancedWithReleases,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How often to deliver `Event::Tick`, if at all.
+    pub tick_rate: Option<Duration>,
+    pub interrupt: Interrupt,
+    /// Keys that suspend the app like `SIGTSTP` does. Keys not listed here arrive as input.
+    pub suspend_keys: Vec<KeyEvent>,
+    pub viewport: Viewport,
+    pub keyboard: Keyboard,
+    /// Whether to deliver `Event::FocusGained` and `Event::FocusLost`.
+    pub focus_events: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tick_rate: None,
+            interrupt: Interrupt::default(),
+            suspend_keys: vec![KeyEvent::new(KeyCode::Char('z'), Modifiers::CTRL)],
+            viewport: Viewport::Fullscreen,
+            keyboard: Keyboard::Enhanced,
+            focus_events: false,
+        }
     }
-    Ok(vec)
 }
 
-pub fn run<C: Canvas, M: Model, E, I: Init<C, M>, V: View<C, M>, U: Update<C, M, E>>(
+pub fn run<
+    C: Canvas,
+    M: Model,
+    E: Send + 'static,
+    R: Transition<E>,
+    I: Init<C, M, E>,
+    V: Draw<C, M, K>,
+    U: Update<C, M, E, R>,
+    K,
+>(
+    init: &I,
+    view: &V,
+    update: &U,
+    options: &Options,
+) -> io::Result<Exit<M>> {
+    let mut backend = TermionBackend::new()?;
+    run_with_backend(&mut backend, init, view, update, options)
+}
+
+/// Runs the app and exits the process with its exit code once it stops.
+pub fn run_and_exit<
+    C: Canvas,
+    M: Model,
+    E: Send + 'static,
+    R: Transition<E>,
+    I: Init<C, M, E>,
+    V: Draw<C, M, K>,
+    U: Update<C, M, E, R>,
+    K,
+>(
     init: &I,
     view: &V,
     update: &U,
-    refresh_interval: Option<Duration>,
+    options: &Options,
 ) -> io::Result<()> {
-    let stdout = io::stdout();
-    let mut stdout = MouseTerminal::from(stdout.lock().into_raw_mode()?);
-    let mut inputs = async_stdin().events();
-    let mut interrupted = false;
-    let mut screen: HashMap<Position, TermPixel> = HashMap::new();
-
-    let (canvas, mut model) = init()?;
-    let mut updates = render(&canvas, &model, view)?;
+    let exit = run(init, view, update, options)?;
+    drop(exit.model);
+    std::process::exit(exit.code as i32);
+}
+
+pub fn run_with_backend<
+    B: Backend,
+    C: Canvas,
+    M: Model,
+    E: Send + 'static,
+    R: Transition<E>,
+    I: Init<C, M, E>,
+    V: Draw<C, M, K>,
+    U: Update<C, M, E, R>,
+    K,
+>(
+    backend: &mut B,
+    init: &I,
+    view: &V,
+    update: &U,
+    options: &Options,
+) -> io::Result<Exit<M>> {
+    let mut terminal = Session::start(backend, options)?;
+    let mut scheduler = Scheduler::new(options.tick_rate, terminal.waker());
+    let mut interrupter = Interrupter::new(&options.interrupt, terminal.waker())?;
+    let mut suspender = Suspender::new(&options.suspend_keys, terminal.waker())?;
+    let size = terminal.size()?;
+    let size = terminal.frame_size(size);
+    let mut screen = Buffer::new(size);
+    let mut frame = Buffer::new(size);
+    let mut dirty = true;
+    let mut stale = false;
+
+    let (canvas, mut model) = init(&Context::new(scheduler.handle(), terminal.capabilities()))?;
     let mut event = Event::NoOp;
 
-    write!(stdout, "{}", termion::cursor::Hide)?;
     loop {
-        if !interrupted {
-            if updates.len() != 0 as usize {
-                for (position, (ascii, style)) in updates.iter() {
-                    let &(x, y) = position;
-                    write!(
-                        stdout,
-                        "{}{}",
-                        termion::cursor::Goto(x, y),
-                        style.paint(ascii.to_string())
-                    )?;
+        match event {
+            Event::Stop => {
+                terminal.end()?;
+                return Ok(Exit {
+                    model,
+                    code: ExitCode::OK,
+                });
+            }
+            Event::NoOp => {
+                if dirty {
+                    frame.clear();
+                    view.draw(&canvas, &model, &mut frame)?;
+                    let origin = terminal.origin();
+                    paint(&mut *terminal, &mut screen, &frame, stale, origin)?;
+                    dirty = false;
+                    stale = false;
                 }
 
-                stdout.flush()?;
-                updates.clear();
-            }
+                let now = Instant::now();
+                let mut request = interrupter.poll(now);
+                let mut suspend = request.is_none() && suspender.poll_suspend();
 
-            for (position, (ascii, style)) in render(&canvas, &model, view)? {
-                if let Some((curr_ascii, curr_style)) = screen.get(&position) {
-                    if curr_ascii == &ascii && curr_style == &style {
+                // Everything else waits while a stop or a suspension is pending.
+                if request.is_none() && !suspend {
+                    if suspender.poll_resume() {
+                        terminal.resume()?;
+                        stale = true;
+                        dirty = true;
                         continue;
-                    };
-                };
+                    }
 
-                updates.push((position, (ascii, style)));
-                screen.insert(position, (ascii, style));
-            }
-        }
+                    if let Some((mut command, f)) = scheduler.next_exec() {
+                        let status = terminal.exec(&mut command)?;
+                        interrupter.discard();
+                        suspender.discard();
+                        stale = true;
+                        event = Event::Msg(f(status));
+                    } else if let Some(new_size) = terminal.poll_resize()? {
+                        terminal.fit(new_size);
+                        let new_size = terminal.frame_size(new_size);
+                        screen.resize(new_size);
+                        frame.resize(new_size);
+                        stale = true;
+                        event = Event::Resize(new_size);
+                    } else if let Some(scheduled) = scheduler.next(now)? {
+                        event = scheduled;
+                    } else {
+                        let deadline = match (scheduler.deadline(), interrupter.deadline()) {
+                            (Some(a), Some(b)) => Some(a.min(b)),
+                            (a, b) => a.or(b),
+                        };
+                        let timeout =
+                            deadline.map(|deadline| deadline.saturating_duration_since(now));
 
-        match event {
-            Event::Stop => {
-                writeln!(stdout, "{}", termion::cursor::Show)?;
-                drop(stdout);
-                drop(inputs);
-                drop(model);
-                std::process::exit(ExitCode::OK as i32);
-            }
-            Event::NoOp => {
-                if let Some(Ok(input)) = inputs.next() {
-                    match input {
-                        Input::Key(k) => match k {
-                            Key::Ctrl('c') => match interrupted {
-                                false => {
-                                    interrupted = true;
-                                    update(&canvas, &mut model, &Event::GracefulStop)?;
+                        match terminal.poll_input(timeout) {
+                            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                                if scheduler.is_busy() || interrupter.deadline().is_some() {
+                                    scheduler.wait(timeout);
+                                } else {
                                     event = Event::Stop;
                                 }
-                                true => {
-                                    std::process::exit(ExitCode::ForcefulStop as i32);
-                                }
-                            },
-                            _ => {
-                                event = Event::Input(input);
+                                continue;
+                            }
+                            Err(e) => return Err(e),
+                            Ok(Some(TerminalEvent::Input(Input::Key(key))))
+                                if interrupter.is_interrupt_key(&key) =>
+                            {
+                                request = Some(interrupter.request(now));
+                            }
+                            Ok(Some(TerminalEvent::Input(Input::Key(key))))
+                                if suspender.is_suspend_key(&key) =>
+                            {
+                                suspend = true;
                             }
-                        },
-                        _ => {
-                            event = Event::Input(input);
+                            Ok(Some(input)) => event = input.into(),
+                            Ok(None) => continue,
                         }
                     }
-                };
+                }
+
+                if suspend {
+                    suspender.suspending();
+                    terminal.suspend()?;
+                    stale = true;
+                    dirty = true;
+                    continue;
+                }
+
+                match request {
+                    Some(Request::Graceful) => event = Event::GracefulStop,
+                    Some(Request::Forceful) => {
+                        terminal.end()?;
+                        return Ok(Exit {
+                            model,
+                            code: ExitCode::ForcefulStop,
+                        });
+                    }
+                    None => {}
+                }
             }
             _ => {}
         }
 
-        if interrupted {
-            continue;
+        let graceful = matches!(event, Event::GracefulStop);
+        let (next, cmd) = update(&canvas, &mut model, &event)?.split();
+        scheduler.execute(cmd);
+        // Without a timeout, `update` only gets to see the stop coming.
+        event = match options.interrupt.timeout {
+            None if graceful => Event::Stop,
+            _ => next,
+        };
+        dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::app::{run_with_backend, Options, Viewport};
+    use crate::canvas::Canvas;
+    use crate::event::{Event, Input, KeyCode, KeyEvent, Modifiers, TerminalEvent};
+    use crate::exit_code::ExitCode;
+    use crate::interrupt::Interrupt;
+    use crate::sys;
+    use crate::testing::{assert_text_eq, TestBackend};
+    use crate::types::*;
+    use signal_hook::consts::{SIGINT, SIGTERM, SIGTSTP};
+    use std::io;
+    use std::panic;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    struct MyCanvas {}
+
+    impl Canvas for MyCanvas {
+        fn top_left_corner(&self) -> io::Result<Position> {
+            Ok((1, 1))
         }
+        fn bottom_right_corner(&self) -> io::Result<Position> {
+            Ok((3, 3))
+        }
+    }
 
-        event = update(&canvas, &mut model, &event)?;
+    fn init(_: &Context<()>) -> io::Result<(MyCanvas, ())> {
+        Ok((MyCanvas {}, ()))
+    }
 
-        if let Some(interval) = refresh_interval {
-            std::thread::sleep(interval);
+    fn view(_: &MyCanvas, _: &(), _: &Position) -> io::Result<Option<TermPixel>> {
+        Ok(None)
+    }
+
+    fn failing_update(_: &MyCanvas, _: &mut (), event: &Event<()>) -> io::Result<Event<()>> {
+        match event {
+            Event::Input(_) => Err(io::Error::other("boom")),
+            _ => Ok(Event::NoOp),
+        }
+    }
+
+    #[test]
+    fn it_restores_the_terminal_on_error() {
+        let mut backend =
+            TestBackend::new(3, 3).with_inputs(vec![Input::Key(KeyCode::Char('x').into())]);
+        assert!(run_with_backend(
+            &mut backend,
+            &init,
+            &view,
+            &failing_update,
+            &Options::default()
+        )
+        .is_err());
+
+        assert!(!backend.is_raw_mode());
+        assert!(!backend.is_alternate_screen());
+        assert!(!backend.is_mouse_enabled());
+        assert!(backend.is_cursor_visible());
+    }
+
+    fn panicking_update(_: &MyCanvas, _: &mut (), event: &Event<()>) -> io::Result<Event<()>> {
+        match event {
+            Event::Input(_) => panic!("boom"),
+            _ => Ok(Event::NoOp),
+        }
+    }
+
+    #[test]
+    fn it_restores_the_terminal_on_panic() {
+        let mut backend =
+            TestBackend::new(3, 3).with_inputs(vec![Input::Key(KeyCode::Char('x').into())]);
+        let restorations = backend.restorations();
+        let seen = Arc::new(AtomicUsize::new(usize::MAX));
+        let owner = thread::current().id();
+        let previous = seen.clone();
+        panic::set_hook(Box::new(move |_| {
+            if thread::current().id() == owner {
+                previous.store(restorations.load(Ordering::SeqCst), Ordering::SeqCst);
+            }
+        }));
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            run_with_backend(
+                &mut backend,
+                &init,
+                &view,
+                &panicking_update,
+                &Options::default(),
+            )
+        }));
+        let _ = panic::take_hook();
+
+        assert!(result.is_err());
+        assert_eq!(seen.load(Ordering::SeqCst), 1);
+        assert_eq!(backend.restorations().load(Ordering::SeqCst), 1);
+        assert!(!backend.is_raw_mode());
+        assert!(!backend.is_alternate_screen());
+        assert!(backend.is_cursor_visible());
+    }
+
+    fn resize_view(
+        _: &MyCanvas,
+        model: &Option<Size>,
+        _: &Position,
+    ) -> io::Result<Option<TermPixel>> {
+        match model {
+            Some(_) => Ok(Some(('r', Default::default()))),
+            None => Ok(Some(('.', Default::default()))),
+        }
+    }
+
+    fn resize_update(
+        _: &MyCanvas,
+        model: &mut Option<Size>,
+        event: &Event<()>,
+    ) -> io::Result<Event<()>> {
+        if let Event::Resize(size) = event {
+            *model = Some(*size);
+        }
+        Ok(Event::NoOp)
+    }
+
+    #[test]
+    fn it_repaints_on_resize() {
+        let mut backend = TestBackend::new(3, 3);
+        backend.push_resize(2, 2);
+        let init = |_: &Context<()>| Ok((MyCanvas {}, None));
+        run_with_backend(
+            &mut backend,
+            &init,
+            &resize_view,
+            &resize_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_text_eq(&backend.frames()[0], &["...", "...", "..."]);
+        assert_text_eq(backend.frame().unwrap(), &["rr", "rr"]);
+    }
+
+    fn inline_view(_: &MyCanvas, _: &(), buffer: &mut Buffer) -> io::Result<()> {
+        buffer.set_string(&(1, 1), "ab", Default::default());
+        buffer.set_string(&(1, 2), "cd", Default::default());
+        Ok(())
+    }
+
+    #[test]
+    fn it_draws_inline_below_the_cursor() {
+        let mut backend = TestBackend::new(3, 3).with_cursor((1, 3));
+        let options = Options {
+            viewport: Viewport::Inline(2),
+            ..Default::default()
+        };
+        run_with_backend(&mut backend, &init, &inline_view, &failing_update, &options).unwrap();
+
+        let frames = backend.frames();
+        assert_text_eq(&frames[frames.len() - 2], &["   ", "ab ", "cd "]);
+        assert_text_eq(&frames[frames.len() - 1], &["ab ", "cd ", "   "]);
+        assert!(!backend.is_alternate_screen());
+        assert!(!backend.is_mouse_enabled());
+    }
+
+    #[test]
+    fn it_delivers_ticks_at_the_tick_rate() {
+        let rate = Duration::from_millis(10);
+        let mut backend = TestBackend::new(3, 1);
+        backend.push_wait(Duration::from_millis(45));
+        let init = |_: &Context<()>| Ok((MyCanvas {}, Vec::new()));
+        let view = |_: &MyCanvas, _: &Vec<(Instant, Duration)>, _: &mut Buffer| Ok(());
+        let update = |_: &MyCanvas, ticks: &mut Vec<(Instant, Duration)>, event: &Event<()>| {
+            if let Event::Tick { now, elapsed } = event {
+                ticks.push((*now, *elapsed));
+            }
+            Ok(Event::NoOp)
+        };
+        let options = Options {
+            tick_rate: Some(rate),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let exit = run_with_backend(&mut backend, &init, &view, &update, &options).unwrap();
+
+        let ticks = exit.model;
+        assert!(!ticks.is_empty());
+        assert!(ticks[0].0 >= start + rate);
+        assert!(ticks.iter().all(|&(_, elapsed)| elapsed >= rate));
+        for pair in ticks.windows(2) {
+            assert_eq!(pair[1].0 - pair[0].0, pair[1].1);
+        }
+    }
+
+    #[test]
+    fn it_moves_an_inline_viewport_up_when_the_screen_shrinks() {
+        let mut backend = TestBackend::new(3, 3).with_cursor((1, 3));
+        backend.push_resize(3, 2);
+        let options = Options {
+            viewport: Viewport::Inline(2),
+            ..Default::default()
+        };
+        run_with_backend(&mut backend, &init, &inline_view, &failing_update, &options).unwrap();
+
+        let frames = backend.frames();
+        assert_text_eq(&frames[frames.len() - 2], &["ab ", "cd "]);
+    }
+
+    #[test]
+    fn it_runs_inline_on_a_screen_without_rows() {
+        let mut backend = TestBackend::new(80, 0);
+        let options = Options {
+            viewport: Viewport::Inline(2),
+            ..Default::default()
+        };
+        run_with_backend(&mut backend, &init, &inline_view, &failing_update, &options).unwrap();
+    }
+
+    #[allow(clippy::ptr_arg)]
+    fn cmd_view(
+        _: &MyCanvas,
+        model: &String,
+        position: &Position,
+    ) -> io::Result<Option<TermPixel>> {
+        let &(x, y) = position;
+        match model.chars().nth(x as usize - 1) {
+            Some(c) if y == 1 => Ok(Some((c, Default::default()))),
+            _ => Ok(None),
+        }
+    }
+
+    fn cmd_update(_: &MyCanvas, model: &mut String, event: &Event<char>) -> io::Result<Cmd<char>> {
+        match event {
+            Event::Input(_) => Ok(Cmd::batch(vec![
+                Cmd::after(Duration::from_millis(50), 'a'),
+                Cmd::perform(|| 'p'),
+            ])),
+            Event::Msg(c) => {
+                model.push(*c);
+                Ok(Cmd::none())
+            }
+            _ => Ok(Cmd::none()),
+        }
+    }
+
+    #[test]
+    fn it_delivers_command_results() {
+        let mut backend =
+            TestBackend::new(3, 1).with_inputs(vec![Input::Key(KeyCode::Char('x').into())]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let exit = run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &cmd_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(exit.model, "pa");
+        assert_eq!(exit.code, ExitCode::OK);
+        assert_text_eq(backend.frame().unwrap(), &["pa "]);
+    }
+
+    #[test]
+    fn it_fails_when_a_command_panics() {
+        let mut backend =
+            TestBackend::new(3, 1).with_inputs(vec![Input::Key(KeyCode::Char('x').into())]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let update = |_: &MyCanvas, _: &mut String, event: &Event<char>| match event {
+            Event::Input(_) => Ok(Cmd::perform(|| panic!("boom"))),
+            _ => Ok(Cmd::none()),
+        };
+        let result = run_with_backend(&mut backend, &init, &cmd_view, &update, &Options::default());
+
+        assert!(matches!(result, Err(e) if e.to_string().contains("boom")));
+        assert_eq!(backend.restorations().load(Ordering::SeqCst), 0);
+        assert!(!backend.is_raw_mode());
+    }
+
+    #[test]
+    fn it_delivers_messages_sent_through_the_handle() {
+        let mut backend = TestBackend::new(3, 1);
+        let init = |ctx: &Context<char>| {
+            ctx.handle().send('h').unwrap();
+            Ok((MyCanvas {}, String::new()))
+        };
+        run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &cmd_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_text_eq(backend.frame().unwrap(), &["h  "]);
+    }
+
+    #[test]
+    fn it_delivers_messages_from_subscribed_receivers() {
+        let mut backend = TestBackend::new(3, 1);
+        backend.push_wait(Duration::from_millis(50));
+        let init = |ctx: &Context<char>| {
+            let (sender, receiver) = mpsc::channel();
+            ctx.subscribe(receiver);
+            sender.send('s').unwrap();
+            sender.send('t').unwrap();
+            Ok((MyCanvas {}, String::new()))
+        };
+        let exit = run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &cmd_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(exit.model, "st");
+    }
+
+    fn stop_update(
+        _: &MyCanvas,
+        model: &mut String,
+        event: &Event<char>,
+    ) -> io::Result<Event<char>> {
+        match event {
+            Event::Input(Input::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: Modifiers::CTRL,
+                ..
+            })) => model.push(*c),
+            Event::GracefulStop => model.push('g'),
+            _ => {}
         }
+        Ok(Event::NoOp)
+    }
+
+    #[test]
+    fn it_stops_after_a_graceful_stop() {
+        let ctrl_c = Input::Key(KeyEvent::new(KeyCode::Char('c'), Modifiers::CTRL));
+        let ctrl_x = Input::Key(KeyEvent::new(KeyCode::Char('x'), Modifiers::CTRL));
+        let mut backend = TestBackend::new(3, 1).with_inputs(vec![ctrl_c, ctrl_x]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let exit = run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &stop_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(exit.model, "g");
+        assert_eq!(exit.code, ExitCode::OK);
+    }
+
+    #[test]
+    fn it_stops_forcefully_on_a_second_interrupt() {
+        let ctrl_c = Input::Key(KeyEvent::new(KeyCode::Char('c'), Modifiers::CTRL));
+        let mut backend = TestBackend::new(3, 1).with_inputs(vec![ctrl_c.clone(), ctrl_c]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let options = Options {
+            interrupt: Interrupt {
+                timeout: Some(Duration::from_secs(60)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let exit =
+            run_with_backend(&mut backend, &init, &cmd_view, &stop_update, &options).unwrap();
+
+        assert_eq!(exit.model, "g");
+        assert_eq!(exit.code, ExitCode::ForcefulStop);
+        assert!(!backend.is_raw_mode());
+    }
+
+    #[test]
+    fn it_stops_forcefully_once_the_timeout_runs_out() {
+        let mut backend = TestBackend::new(3, 1).with_inputs(vec![Input::Key(KeyEvent::new(
+            KeyCode::Char('c'),
+            Modifiers::CTRL,
+        ))]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let options = Options {
+            interrupt: Interrupt {
+                timeout: Some(Duration::from_millis(20)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let exit =
+            run_with_backend(&mut backend, &init, &cmd_view, &stop_update, &options).unwrap();
+
+        assert_eq!(exit.model, "g");
+        assert_eq!(exit.code, ExitCode::ForcefulStop);
+    }
+
+    #[test]
+    fn it_puts_signal_handling_back_once_it_stops() {
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let mut backend = TestBackend::new(3, 1);
+        run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &stop_update,
+            &Options::default(),
+        )
+        .unwrap();
+        drop(backend);
+
+        for signal in [SIGINT, SIGTERM, SIGTSTP] {
+            let action = sys::signal_action(signal).unwrap();
+            assert_eq!(action.sa_sigaction, libc::SIG_DFL);
+        }
+
+        // Signals are still caught by the next app.
+        let mut backend = TestBackend::new(3, 1);
+        backend.push_wait(Duration::from_millis(50));
+        let init = |_: &Context<char>| {
+            signal_hook::low_level::raise(SIGTERM)?;
+            Ok((MyCanvas {}, String::new()))
+        };
+        let exit = run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &stop_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(exit.model, "g");
+    }
+
+    #[test]
+    fn it_passes_interrupt_keys_through_when_asked_to() {
+        let mut backend = TestBackend::new(3, 1).with_inputs(vec![Input::Key(KeyEvent::new(
+            KeyCode::Char('c'),
+            Modifiers::CTRL,
+        ))]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let options = Options {
+            interrupt: Interrupt::none(),
+            ..Default::default()
+        };
+        let exit =
+            run_with_backend(&mut backend, &init, &cmd_view, &stop_update, &options).unwrap();
+
+        assert_eq!(exit.model, "c");
+        assert_eq!(exit.code, ExitCode::OK);
+    }
+
+    fn exec_update(_: &MyCanvas, model: &mut String, event: &Event<char>) -> io::Result<Cmd<char>> {
+        match event {
+            Event::Input(_) => {
+                let mut command = Command::new("sh");
+                command.args(["-c", "exit 3"]);
+                Ok(Cmd::exec(command, |status| match status {
+                    Ok(status) => char::from_digit(status.code().unwrap_or(0) as u32, 10).unwrap(),
+                    Err(_) => 'e',
+                }))
+            }
+            Event::Msg(c) => {
+                model.push(*c);
+                Ok(Cmd::none())
+            }
+            _ => Ok(Cmd::none()),
+        }
+    }
+
+    #[test]
+    fn it_delivers_the_exit_status_of_external_processes() {
+        let mut backend =
+            TestBackend::new(3, 1).with_inputs(vec![Input::Key(KeyCode::Char('x').into())]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let exit = run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &exec_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(exit.model, "3");
+        assert_text_eq(backend.frame().unwrap(), &["3  "]);
+        assert!(!backend.is_raw_mode());
+    }
+
+    #[test]
+    fn it_suspends_on_ctrl_z() {
+        let inputs = vec![
+            Input::Key(KeyEvent::new(KeyCode::Char('z'), Modifiers::CTRL)),
+            Input::Key(KeyCode::Char('x').into()),
+        ];
+        let mut backend = TestBackend::new(3, 1).with_inputs(inputs);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        let exit = run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &stop_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(backend.suspensions(), 1);
+        assert_eq!(exit.model, "");
+        assert!(!backend.is_alternate_screen());
+    }
+
+    #[allow(clippy::ptr_arg)]
+    fn immediate_view(_: &MyCanvas, model: &String, buffer: &mut Buffer) -> io::Result<()> {
+        buffer.set_string(&(2, 1), model, Default::default());
+        Ok(())
+    }
+
+    #[test]
+    fn it_draws_immediate_views() {
+        let mut backend =
+            TestBackend::new(3, 1).with_inputs(vec![Input::Key(KeyCode::Char('x').into())]);
+        let init = |_: &Context<char>| Ok((MyCanvas {}, String::new()));
+        run_with_backend(
+            &mut backend,
+            &init,
+            &immediate_view,
+            &cmd_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_text_eq(backend.frame().unwrap(), &[" pa"]);
+    }
+
+    #[allow(clippy::ptr_arg)]
+    fn caret_view(_: &MyCanvas, model: &String, buffer: &mut Buffer) -> io::Result<()> {
+        let end = buffer.set_string(&(1, 1), model, Default::default());
+        if !model.is_empty() {
+            buffer.show_cursor(Cursor::new(end).shape(CursorShape::Bar).steady());
+        }
+        Ok(())
+    }
+
+    fn typing_update(_: &MyCanvas, model: &mut String, event: &Event<()>) -> io::Result<Event<()>> {
+        match event {
+            Event::Input(Input::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            })) => model.push(*c),
+            Event::Paste(text) => model.push_str(text),
+            Event::Input(Input::Key(KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            })) => {
+                model.pop();
+            }
+            _ => {}
+        }
+        Ok(Event::NoOp)
+    }
+
+    #[test]
+    fn it_delivers_pastes_as_a_single_event() {
+        let mut backend =
+            TestBackend::new(4, 1).with_inputs(vec![TerminalEvent::Paste("xyz".to_string())]);
+        let init = |_: &Context<()>| Ok((MyCanvas {}, String::new()));
+        run_with_backend(
+            &mut backend,
+            &init,
+            &immediate_view,
+            &typing_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        let lines: Vec<_> = backend.frames().iter().map(|f| f.lines()).collect();
+        assert_eq!(lines, vec![vec![" xyz"]]);
+        assert!(!backend.is_bracketed_paste());
+    }
+
+    #[test]
+    fn it_delivers_focus_changes_when_asked_to() {
+        let mut backend = TestBackend::new(4, 1)
+            .with_inputs(vec![TerminalEvent::FocusLost, TerminalEvent::FocusGained]);
+        let init = |_: &Context<()>| Ok((MyCanvas {}, String::new()));
+        let update = |_: &MyCanvas, model: &mut String, event: &Event<()>| {
+            match event {
+                Event::FocusLost => model.push('-'),
+                Event::FocusGained => model.push('+'),
+                _ => {}
+            }
+            Ok(Event::NoOp)
+        };
+        let options = Options {
+            focus_events: true,
+            ..Default::default()
+        };
+        run_with_backend(&mut backend, &init, &immediate_view, &update, &options).unwrap();
+
+        assert_text_eq(backend.frame().unwrap(), &[" -+ "]);
+        assert!(!backend.is_focus_events());
+    }
+
+    #[test]
+    fn it_shows_the_cursor_where_the_view_asks_for_it() {
+        let mut backend = TestBackend::new(3, 1).with_inputs(vec![
+            Input::Key(KeyCode::Char('a').into()),
+            Input::Key(KeyCode::Backspace.into()),
+        ]);
+        let init = |_: &Context<()>| Ok((MyCanvas {}, String::new()));
+        run_with_backend(
+            &mut backend,
+            &init,
+            &caret_view,
+            &typing_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        let frames = backend.frames();
+        let typed = &frames[frames.len() - 2];
+        assert_text_eq(typed, &["a  "]);
+        assert_eq!(
+            typed.cursor(),
+            Some(Cursor::new((2, 1)).shape(CursorShape::Bar).steady())
+        );
+        assert_eq!(frames[frames.len() - 1].cursor(), None);
+        assert_eq!(backend.cursor_shape(), None);
+        assert!(backend.is_cursor_visible());
+    }
+
+    fn wide_view(_: &MyCanvas, _: &(), position: &Position) -> io::Result<Option<TermPixel>> {
+        match position {
+            (1, 1) => Ok(Some(('字', Default::default()))),
+            _ => Ok(Some(('.', Default::default()))),
+        }
+    }
+
+    #[test]
+    fn it_skips_the_position_after_a_wide_character() {
+        let mut backend = TestBackend::new(3, 1);
+        run_with_backend(
+            &mut backend,
+            &init,
+            &wide_view,
+            &failing_update,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_text_eq(backend.frame().unwrap(), &["字."]);
+    }
+
+    #[test]
+    fn it_tells_init_about_the_terminal() {
+        let capabilities = Capabilities {
+            styled: false,
+            ..Default::default()
+        };
+        let mut backend = TestBackend::new(3, 1).with_capabilities(capabilities);
+        let init = |ctx: &Context<char>| {
+            assert_eq!(ctx.capabilities(), capabilities);
+            Ok((MyCanvas {}, String::new()))
+        };
+        run_with_backend(
+            &mut backend,
+            &init,
+            &cmd_view,
+            &cmd_update,
+            &Options::default(),
+        )
+        .unwrap();
     }
 }
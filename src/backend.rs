@@ -0,0 +1,690 @@
+use crate::capabilities::Capabilities;
+use crate::cursor::{self, CursorShape};
+use crate::event::TerminalEvent;
+use crate::parser;
+use crate::style::Style;
+use crate::sys;
+use crate::types::*;
+use signal_hook::consts::{SIGSTOP, SIGWINCH};
+use signal_hook::SigId;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const ENTER_MOUSE_SEQUENCE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const EXIT_MOUSE_SEQUENCE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+const ENTER_BRACKETED_PASTE_SEQUENCE: &str = "\x1b[?2004h";
+const EXIT_BRACKETED_PASTE_SEQUENCE: &str = "\x1b[?2004l";
+const ENTER_FOCUS_EVENTS_SEQUENCE: &str = "\x1b[?1004h";
+const EXIT_FOCUS_EVENTS_SEQUENCE: &str = "\x1b[?1004l";
+const BEGIN_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026h";
+const END_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026l";
+const SYNCHRONIZED_OUTPUT_MODE: u16 = 2026;
+
+/// Pushes and pops the flags of the kitty keyboard protocol: 1 disambiguates keys, and 2, 4 and
+/// 8 add repeats and releases, shifted keys and escape codes for text keys, without which
+/// their releases are not reported.
+const ENTER_KEYBOARD_ENHANCEMENT_SEQUENCE: &str = "\x1b[>1u";
+const ENTER_KEYBOARD_ENHANCEMENT_WITH_RELEASES_SEQUENCE: &str = "\x1b[>15u";
+const EXIT_KEYBOARD_ENHANCEMENT_SEQUENCE: &str = "\x1b[<u";
+
+/// Asks for the state of synchronized output and for the keyboard protocol flags, then for the
+/// device attributes, which every terminal reports. Once those arrive there is no point in
+/// waiting for the other answers.
+const FEATURES_QUERY: &str = "\x1b[?2026$p\x1b[?u\x1b[c";
+const CURSOR_POSITION_QUERY: &str = "\x1b[6n";
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long a reply may still arrive after its query timed out. Until then, a late cursor
+/// position report is dropped rather than taken for a key.
+const LATE_REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long the rest of an escape sequence may take before the escape byte is taken for the
+/// Esc key.
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Wakes up a backend blocked in `poll_input` from another thread.
+#[derive(Clone)]
+pub struct Waker(Arc<dyn Fn() + Send + Sync>);
+
+impl Waker {
+    pub fn new<F: Fn() + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn wake(&self) {
+        (self.0)()
+    }
+}
+
+/// Everything `app::run` needs from a terminal: drawing, input and its size.
+pub trait Backend {
+    fn enter_raw_mode(&mut self) -> io::Result<()>;
+    fn leave_raw_mode(&mut self) -> io::Result<()>;
+    fn enable_mouse(&mut self) -> io::Result<()>;
+    fn disable_mouse(&mut self) -> io::Result<()>;
+
+    /// Makes the terminal mark pasted text, so that it arrives as a single `TerminalEvent::Paste`.
+    /// Backends whose input never contains pastes keep the default.
+    fn enable_bracketed_paste(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn disable_bracketed_paste(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Makes the terminal report when its window gains or loses focus. Backends that can't tell
+    /// keep the default.
+    fn enable_focus_events(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn disable_focus_events(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Turns on the kitty keyboard protocol if the terminal has it, optionally with repeats and
+    /// releases. Backends that can't keep the default.
+    fn enable_keyboard_enhancement(&mut self, _releases: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn disable_keyboard_enhancement(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn enter_alternate_screen(&mut self) -> io::Result<()>;
+    fn leave_alternate_screen(&mut self) -> io::Result<()>;
+    fn hide_cursor(&mut self) -> io::Result<()>;
+    fn show_cursor(&mut self) -> io::Result<()>;
+
+    /// Changes how the cursor looks. Terminals that can't ignore it, so backends for them can
+    /// keep the default.
+    fn set_cursor_shape(&mut self, _shape: CursorShape, _blinking: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Brings back the cursor shape the user had before.
+    fn reset_cursor_shape(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()>;
+    fn goto(&mut self, position: Position) -> io::Result<()>;
+
+    /// Blanks the screen from the start of `row` to the end.
+    fn clear_from(&mut self, row: u16) -> io::Result<()>;
+
+    /// Scrolls the whole screen up by `lines`, pushing the top lines into the scrollback.
+    fn scroll_up(&mut self, lines: u16) -> io::Result<()>;
+
+    /// Asks the terminal where the cursor is. Backends that can't tell keep the default.
+    fn cursor_position(&mut self) -> io::Result<Option<Position>> {
+        Ok(None)
+    }
+    fn write_cell(&mut self, cell: &Cell) -> io::Result<()>;
+
+    /// Marks the start of a frame, so that the terminal can show it all at once instead of
+    /// painting it while it is being written. Backends that can't keep the default.
+    fn begin_frame(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Writes the pixels that changed since the last frame, in screen order. The default moves
+    /// the cursor to each of them.
+    fn draw(&mut self, changes: &[(Position, &Cell)]) -> io::Result<()> {
+        for &(position, cell) in changes {
+            self.goto(position)?;
+            self.write_cell(cell)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Waits up to `timeout`, or forever if it is `None`, for the next input. Returns `None` if
+    /// the wait timed out or was interrupted by something else, such as a resize. Fails with
+    /// `UnexpectedEof` once there will be no more input, which stops the app.
+    fn poll_input(&mut self, timeout: Option<Duration>) -> io::Result<Option<TerminalEvent>>;
+
+    fn size(&self) -> io::Result<Size>;
+
+    /// What the terminal can show. The runtime passes this on to `init`.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Returns the new size if the terminal was resized since the last call.
+    fn poll_resize(&mut self) -> io::Result<Option<Size>>;
+
+    /// Stops the process until it is continued. It is called with the terminal already handed
+    /// back to the shell; backends that cannot stop the process keep the default.
+    fn suspend(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns a waker that makes a pending `poll_input` return early. Backends that never
+    /// block can keep the default, which does nothing.
+    fn waker(&self) -> Waker {
+        Waker::new(|| {})
+    }
+
+    /// Returns a function that puts the terminal back into its original state without going
+    /// through the backend. It is called from the panic hook installed by `app::run`.
+    fn restorer(&self) -> Box<dyn Fn() + Send + Sync> {
+        Box::new(|| {})
+    }
+
+    /// Connects a command about to be handed the terminal by `Cmd::exec` to it. By default the
+    /// command gets the standard streams of the process.
+    fn attach(&self, _command: &mut Command) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encodes `changes` with as few bytes as possible: the cursor is only moved when the next
+/// cell is not right after the previous one, and only the difference between consecutive
+/// styles is emitted. The style is reset at the end.
+///
+/// Terminals don't always agree on the width of a grapheme, especially of emoji, so the cursor
+/// is moved explicitly after every wide one.
+fn write_changes<W: Write>(
+    out: &mut W,
+    changes: &[(Position, &Cell)],
+    capabilities: &Capabilities,
+) -> io::Result<()> {
+    let mut cursor = None;
+    let mut current = Style::default();
+
+    for &(position, cell) in changes {
+        if cursor != Some(position) {
+            let (x, y) = position;
+            write!(out, "{}", termion::cursor::Goto(x, y))?;
+        }
+        let style = capabilities.adapt(cell.style());
+        write!(out, "{}{}", current.infix(style), cell.symbol())?;
+        current = style;
+        cursor = match cell.width() {
+            1 => Some((position.0.saturating_add(1), position.1)),
+            _ => None,
+        };
+    }
+
+    write!(out, "{}", current.suffix())
+}
+
+/// Talks to the controlling terminal through `/dev/tty` rather than stdin and stdout, which
+/// are left free for data, as in `ls | picker | xargs rm`.
+pub struct TermionBackend {
+    out: Vec<u8>,
+    tty: File,
+    original: Option<sys::Termios>,
+    synchronized: Option<bool>,
+    keyboard_enhancement: bool,
+    keyboard_enhanced: bool,
+    capabilities: Capabilities,
+    /// Bytes of a paste or an escape sequence that has not ended yet.
+    received: Vec<u8>,
+    escape_deadline: Option<Instant>,
+    /// Until when a cursor position report may still arrive after its query timed out.
+    late_cursor_position: Option<Instant>,
+    inputs: VecDeque<TerminalEvent>,
+    resized: Arc<AtomicBool>,
+    signals: UnixStream,
+    wake: Arc<UnixStream>,
+    signal_ids: Vec<SigId>,
+}
+
+impl TermionBackend {
+    pub fn new() -> io::Result<Self> {
+        Self::with_tty(termion::get_tty()?)
+    }
+
+    /// Uses `tty` for both input and output, such as another terminal device opened for
+    /// reading and writing.
+    pub fn with_tty(tty: File) -> io::Result<Self> {
+        let resized = Arc::new(AtomicBool::new(false));
+        let (signals, wake) = UnixStream::pair()?;
+        signals.set_nonblocking(true)?;
+        wake.set_nonblocking(true)?;
+        let signal_ids = vec![
+            signal_hook::flag::register(SIGWINCH, resized.clone())?,
+            signal_hook::low_level::pipe::register(SIGWINCH, wake.try_clone()?)?,
+        ];
+
+        Ok(Self {
+            out: Vec::new(),
+            capabilities: Capabilities::detect(&tty),
+            tty,
+            original: None,
+            synchronized: None,
+            keyboard_enhancement: false,
+            keyboard_enhanced: false,
+            received: Vec::new(),
+            escape_deadline: None,
+            late_cursor_position: None,
+            inputs: VecDeque::new(),
+            resized,
+            signals,
+            wake: Arc::new(wake),
+            signal_ids,
+        })
+    }
+}
+
+impl TermionBackend {
+    /// Overrides what was detected about the terminal.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Writes `query` and reads from the terminal until `take` finds the reply in what was read
+    /// so far, or the terminal took too long to answer. Anything that is not part of a reply,
+    /// such as keys pressed meanwhile, is kept as input.
+    fn query<T, F: FnMut(&mut Vec<u8>) -> Option<T>>(
+        &mut self,
+        query: &str,
+        mut take: F,
+    ) -> io::Result<Option<T>> {
+        if !sys::is_tty(self.tty.as_raw_fd()) {
+            return Ok(None);
+        }
+
+        write!(self.out, "{}", query)?;
+        self.flush()?;
+
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        let mut received = Vec::new();
+        let mut reply = None;
+        while reply.is_none() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            if !sys::poll_read(&[self.tty.as_raw_fd()], Some(deadline - now))?[0] {
+                continue;
+            }
+
+            let mut buf = [0; 1024];
+            let n = self.tty.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            received.extend_from_slice(&buf[..n]);
+            reply = take(&mut received);
+        }
+        self.received.extend(received);
+        parser::parse(&mut self.received, &mut self.inputs);
+        Ok(reply)
+    }
+
+    /// Asks the terminal whether it supports synchronized output (DEC mode 2026) and the kitty
+    /// keyboard protocol.
+    fn query_features(&mut self) -> io::Result<()> {
+        let mut modes = Vec::new();
+        let mut keyboard_flags = None;
+        self.query(FEATURES_QUERY, |received| {
+            let replies = parser::take_replies(received);
+            modes.extend(replies.modes);
+            keyboard_flags = keyboard_flags.or(replies.keyboard_flags);
+            replies.device_attributes.then_some(())
+        })?;
+
+        // 1 and 2 mean set and reset, 3 permanently set; 0 and 4 mean it can't be used.
+        self.synchronized =
+            Some(modes.iter().any(|&(mode, state)| {
+                mode == SYNCHRONIZED_OUTPUT_MODE && (1..=3).contains(&state)
+            }));
+        self.keyboard_enhancement = keyboard_flags.is_some();
+        Ok(())
+    }
+}
+
+impl Drop for TermionBackend {
+    fn drop(&mut self) {
+        for id in self.signal_ids.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+impl Backend for TermionBackend {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        if self.original.is_none() {
+            let fd = self.tty.as_raw_fd();
+            let original = sys::get_termios(fd)?;
+            sys::set_termios(fd, &sys::raw_termios(&original))?;
+            self.original = Some(original);
+        }
+
+        if self.synchronized.is_none() {
+            self.query_features()?;
+        }
+        Ok(())
+    }
+
+    fn leave_raw_mode(&mut self) -> io::Result<()> {
+        if let Some(original) = self.original.take() {
+            sys::set_termios(self.tty.as_raw_fd(), &original)?;
+        }
+        Ok(())
+    }
+
+    fn enable_mouse(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", ENTER_MOUSE_SEQUENCE)
+    }
+
+    fn disable_mouse(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", EXIT_MOUSE_SEQUENCE)
+    }
+
+    fn enable_bracketed_paste(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", ENTER_BRACKETED_PASTE_SEQUENCE)
+    }
+
+    fn disable_bracketed_paste(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", EXIT_BRACKETED_PASTE_SEQUENCE)
+    }
+
+    fn enable_focus_events(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", ENTER_FOCUS_EVENTS_SEQUENCE)
+    }
+
+    fn disable_focus_events(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", EXIT_FOCUS_EVENTS_SEQUENCE)
+    }
+
+    fn enable_keyboard_enhancement(&mut self, releases: bool) -> io::Result<()> {
+        if !self.keyboard_enhancement || self.keyboard_enhanced {
+            return Ok(());
+        }
+        self.keyboard_enhanced = true;
+        match releases {
+            true => write!(
+                self.out,
+                "{}",
+                ENTER_KEYBOARD_ENHANCEMENT_WITH_RELEASES_SEQUENCE
+            ),
+            false => write!(self.out, "{}", ENTER_KEYBOARD_ENHANCEMENT_SEQUENCE),
+        }
+    }
+
+    fn disable_keyboard_enhancement(&mut self) -> io::Result<()> {
+        if !self.keyboard_enhanced {
+            return Ok(());
+        }
+        self.keyboard_enhanced = false;
+        write!(self.out, "{}", EXIT_KEYBOARD_ENHANCEMENT_SEQUENCE)
+    }
+
+    fn enter_alternate_screen(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", termion::screen::ToAlternateScreen)
+    }
+
+    fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", termion::screen::ToMainScreen)
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", termion::cursor::Hide)
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", termion::cursor::Show)
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> io::Result<()> {
+        write!(self.out, "{}", cursor::shape_sequence(shape, blinking))
+    }
+
+    fn reset_cursor_shape(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", cursor::RESET_SHAPE_SEQUENCE)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        write!(self.out, "{}", termion::clear::All)
+    }
+
+    fn goto(&mut self, position: Position) -> io::Result<()> {
+        let (x, y) = position;
+        write!(self.out, "{}", termion::cursor::Goto(x, y))
+    }
+
+    fn clear_from(&mut self, row: u16) -> io::Result<()> {
+        write!(
+            self.out,
+            "{}{}",
+            termion::cursor::Goto(1, row),
+            termion::clear::AfterCursor
+        )
+    }
+
+    /// Line feeds on the last row, which unlike `CSI S` put the lines into the scrollback on
+    /// every terminal.
+    fn scroll_up(&mut self, lines: u16) -> io::Result<()> {
+        if lines == 0 {
+            return Ok(());
+        }
+        let (_, rows) = self.size()?;
+        write!(self.out, "{}", termion::cursor::Goto(1, rows))?;
+        self.out.extend(std::iter::repeat_n(b'\n', lines as usize));
+        Ok(())
+    }
+
+    fn cursor_position(&mut self) -> io::Result<Option<Position>> {
+        let position = self.query(CURSOR_POSITION_QUERY, parser::take_cursor_position)?;
+        if position.is_none() {
+            self.late_cursor_position = Some(Instant::now() + LATE_REPLY_TIMEOUT);
+        }
+        Ok(position)
+    }
+
+    fn write_cell(&mut self, cell: &Cell) -> io::Result<()> {
+        let style = self.capabilities.adapt(cell.style());
+        write!(
+            self.out,
+            "{}{}{}",
+            style.prefix(),
+            cell.symbol(),
+            style.suffix()
+        )
+    }
+
+    fn begin_frame(&mut self) -> io::Result<()> {
+        match self.synchronized {
+            Some(true) => write!(self.out, "{}", BEGIN_SYNCHRONIZED_UPDATE),
+            _ => Ok(()),
+        }
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        match self.synchronized {
+            Some(true) => write!(self.out, "{}", END_SYNCHRONIZED_UPDATE),
+            _ => Ok(()),
+        }
+    }
+
+    fn draw(&mut self, changes: &[(Position, &Cell)]) -> io::Result<()> {
+        write_changes(&mut self.out, changes, &self.capabilities)
+    }
+
+    /// Everything written since the last flush goes out in a single write.
+    fn flush(&mut self) -> io::Result<()> {
+        self.tty.write_all(&self.out)?;
+        self.out.clear();
+        self.tty.flush()
+    }
+
+    fn poll_input(&mut self, timeout: Option<Duration>) -> io::Result<Option<TerminalEvent>> {
+        if let Some(input) = self.inputs.pop_front() {
+            return Ok(Some(input));
+        }
+
+        // An escape sequence cut short gets a little time to arrive in full, after which it
+        // is taken for what it is, usually a lone Esc.
+        let now = Instant::now();
+        self.escape_deadline = match parser::is_incomplete(&self.received) {
+            true => Some(self.escape_deadline.unwrap_or(now + ESCAPE_TIMEOUT)),
+            false => None,
+        };
+        let timeout = match self.escape_deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(now);
+                Some(timeout.map_or(left, |timeout| timeout.min(left)))
+            }
+            None => timeout,
+        };
+
+        let fds = [self.tty.as_raw_fd(), self.signals.as_raw_fd()];
+        let ready = sys::poll_read(&fds, timeout)?;
+
+        if ready[1] {
+            let mut buf = [0; 64];
+            while let Ok(n) = self.signals.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+            }
+        }
+
+        if ready[0] {
+            let mut buf = [0; 1024];
+            let n = self.tty.read(&mut buf)?;
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the terminal hung up",
+                ));
+            }
+            self.received.extend_from_slice(&buf[..n]);
+            if self
+                .late_cursor_position
+                .is_some_and(|deadline| Instant::now() < deadline)
+                && parser::take_cursor_position(&mut self.received).is_some()
+            {
+                self.late_cursor_position = None;
+            }
+            parser::parse(&mut self.received, &mut self.inputs);
+        } else if self
+            .escape_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            parser::flush(&mut self.received, &mut self.inputs);
+        }
+
+        Ok(self.inputs.pop_front())
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        sys::window_size(self.tty.as_raw_fd())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn poll_resize(&mut self) -> io::Result<Option<Size>> {
+        if self.resized.swap(false, Ordering::SeqCst) {
+            self.size().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        signal_hook::low_level::raise(SIGSTOP)
+    }
+
+    fn waker(&self) -> Waker {
+        let wake = self.wake.clone();
+        Waker::new(move || {
+            let _ = (&*wake).write(&[1]);
+        })
+    }
+
+    /// The terminal may not be on our standard streams, e.g. in the middle of a pipeline.
+    fn attach(&self, command: &mut Command) -> io::Result<()> {
+        command
+            .stdin(Stdio::from(self.tty.try_clone()?))
+            .stdout(Stdio::from(self.tty.try_clone()?))
+            .stderr(Stdio::from(self.tty.try_clone()?));
+        Ok(())
+    }
+
+    fn restorer(&self) -> Box<dyn Fn() + Send + Sync> {
+        let tty = self.tty.try_clone().ok();
+        let original = self.original;
+        let keyboard = match self.keyboard_enhanced {
+            true => EXIT_KEYBOARD_ENHANCEMENT_SEQUENCE,
+            false => "",
+        };
+        Box::new(move || {
+            let mut tty = match &tty {
+                Some(tty) => tty,
+                None => return,
+            };
+            let _ = write!(
+                tty,
+                "{}{}{}{}{}{}{}",
+                EXIT_MOUSE_SEQUENCE,
+                EXIT_BRACKETED_PASTE_SEQUENCE,
+                keyboard,
+                EXIT_FOCUS_EVENTS_SEQUENCE,
+                termion::screen::ToMainScreen,
+                cursor::RESET_SHAPE_SEQUENCE,
+                termion::cursor::Show
+            );
+            let _ = tty.flush();
+            if let Some(original) = original {
+                let _ = sys::set_termios(tty.as_raw_fd(), &original);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::write_changes;
+    use crate::buffer::Cell;
+    use crate::capabilities::Capabilities;
+    use crate::style::{Color, ColorDepth, Style};
+
+    #[test]
+    fn it_coalesces_cursor_moves_and_styles() {
+        let red = Style::default().fg(Color::Red);
+        let orange = Color::Rgb(255, 135, 0).normal();
+        let plain = Cell::new("b", Style::default());
+        let wide = Cell::new("字", Style::default());
+        let changes = [
+            ((1, 1), &Cell::new("a", red)),
+            ((2, 1), &Cell::new("b", red)),
+            ((3, 1), &plain),
+            ((1, 2), &plain),
+            ((2, 2), &wide),
+            ((4, 2), &plain),
+            ((5, 2), &Cell::new("c", orange)),
+        ];
+        let mut out = Vec::new();
+        let capabilities = Capabilities {
+            color_depth: ColorDepth::Ansi256,
+            ..Default::default()
+        };
+        write_changes(&mut out, &changes, &capabilities).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[1;1H\x1b[31mab\x1b[39mb\x1b[2;1Hb字\x1b[2;4Hb\x1b[38;5;208mc\x1b[0m"
+        );
+    }
+}
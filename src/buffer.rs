@@ -0,0 +1,353 @@
+use crate::cursor::Cursor;
+use crate::style::Style;
+use crate::types::*;
+use std::io;
+use std::slice::Chunks;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// What a single column of the screen shows: a grapheme cluster and its style. A wide
+/// grapheme, such as a CJK character or most emoji, takes two columns, the second of which
+/// holds a continuation cell with no symbol of its own.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cell {
+    symbol: String,
+    width: u16,
+    style: Style,
+}
+
+impl Cell {
+    /// `grapheme` is expected to be a single grapheme cluster. It is given at least one column.
+    pub fn new(grapheme: &str, style: Style) -> Self {
+        Self {
+            symbol: grapheme.to_string(),
+            width: grapheme.width().clamp(1, 2) as u16,
+            style,
+        }
+    }
+
+    fn continuation(style: Style) -> Self {
+        Self {
+            symbol: String::new(),
+            width: 0,
+            style,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// The number of columns taken, zero for a continuation cell.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    pub fn is_continuation(&self) -> bool {
+        self.width == 0
+    }
+
+    /// Turns this into a blank cell, keeping the allocation.
+    fn reset(&mut self) {
+        self.symbol.clear();
+        self.symbol.push(' ');
+        self.width = 1;
+        self.style = Style::default();
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self::new(" ", Style::default())
+    }
+}
+
+impl From<TermPixel> for Cell {
+    fn from(pixel: TermPixel) -> Self {
+        let (ascii, style) = pixel;
+        Self {
+            symbol: ascii.to_string(),
+            width: ascii.width().unwrap_or(1).clamp(1, 2) as u16,
+            style,
+        }
+    }
+}
+
+/// A grid of cells covering the whole screen, indexed by 1-based positions like the rest of
+/// the crate. The runtime keeps one for what is on the screen and one for the frame being
+/// rendered, and only writes out their difference.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Buffer {
+    size: Size,
+    cells: Vec<Cell>,
+    cursor: Option<Cursor>,
+}
+
+impl Buffer {
+    /// A buffer of blank cells, which is what a freshly cleared screen looks like.
+    pub fn new(size: Size) -> Self {
+        let (w, h) = size;
+        Self {
+            size,
+            cells: vec![Cell::default(); w as usize * h as usize],
+            cursor: None,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    fn index(&self, position: &Position) -> Option<usize> {
+        let &(x, y) = position;
+        let (w, h) = self.size;
+        if x < 1 || y < 1 || x > w || y > h {
+            None
+        } else {
+            Some((y - 1) as usize * w as usize + (x - 1) as usize)
+        }
+    }
+
+    fn position(&self, index: usize) -> Position {
+        let w = self.size.0 as usize;
+        ((index % w) as u16 + 1, (index / w) as u16 + 1)
+    }
+
+    pub fn get(&self, position: &Position) -> Option<&Cell> {
+        self.index(position).map(|i| &self.cells[i])
+    }
+
+    /// Cells outside of the buffer are ignored. A wide cell also takes the next column, and
+    /// is replaced with a blank one if there is no room for it. Partly overwritten wide cells
+    /// are blanked.
+    pub fn set<P: Into<Cell>>(&mut self, position: &Position, cell: P) {
+        let i = match self.index(position) {
+            Some(i) => i,
+            None => return,
+        };
+        let mut cell = cell.into();
+        let x = position.0;
+
+        self.unlink(i, x);
+        if cell.width == 2 {
+            if x == self.size.0 {
+                cell = Cell::new(" ", cell.style);
+            } else {
+                self.unlink(i + 1, x + 1);
+                self.cells[i + 1] = Cell::continuation(cell.style);
+            }
+        }
+        self.cells[i] = cell;
+    }
+
+    /// Blanks the other half of a wide cell, if `i` is part of one.
+    fn unlink(&mut self, i: usize, x: u16) {
+        match self.cells[i].width {
+            0 if x > 1 => self.cells[i - 1].reset(),
+            2 if x < self.size.0 => self.cells[i + 1].reset(),
+            _ => {}
+        }
+    }
+
+    /// Writes `text` rightwards from `position`, one cell per grapheme, cutting it off at the
+    /// right edge. Returns the position right after it.
+    pub fn set_string(&mut self, position: &Position, text: &str, style: Style) -> Position {
+        let &(mut x, y) = position;
+        for grapheme in text.graphemes(true) {
+            let cell = Cell::new(grapheme, style);
+            let width = cell.width;
+            if x as u32 + width as u32 - 1 > self.size.0 as u32 {
+                break;
+            }
+            self.set(&(x, y), cell);
+            x = x.saturating_add(width);
+        }
+        (x, y)
+    }
+
+    /// Writes styled strings one after another, as `set_string` does.
+    pub fn set_spans(&mut self, position: &Position, spans: &[(&str, Style)]) -> Position {
+        spans.iter().fold(*position, |position, &(text, style)| {
+            self.set_string(&position, text, style)
+        })
+    }
+
+    /// Fills the area covered by a canvas.
+    pub fn fill<C: Canvas>(&mut self, area: &C, pixel: TermPixel) -> io::Result<()> {
+        let (x1, y1) = area.top_left_corner()?;
+        let (x2, y2) = area.bottom_right_corner()?;
+        for y in y1..(y2 + 1) {
+            for x in x1..(x2 + 1) {
+                self.set(&(x, y), pixel);
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws a box along the boundary of a canvas.
+    pub fn set_border<C: Canvas>(&mut self, area: &C, style: Style) -> io::Result<()> {
+        let (x1, y1) = area.top_left_corner()?;
+        let (x2, y2) = area.bottom_right_corner()?;
+        for x in x1..(x2 + 1) {
+            self.set(&(x, y1), ('─', style));
+            self.set(&(x, y2), ('─', style));
+        }
+        for y in y1..(y2 + 1) {
+            self.set(&(x1, y), ('│', style));
+            self.set(&(x2, y), ('│', style));
+        }
+        self.set(&(x1, y1), ('┌', style));
+        self.set(&(x2, y1), ('┐', style));
+        self.set(&(x1, y2), ('└', style));
+        self.set(&(x2, y2), ('┘', style));
+        Ok(())
+    }
+
+    /// Draws a sub-view, usually with a canvas covering only part of the screen.
+    pub fn render<C: Canvas, M: Model, D: Draw<C, M, K>, K>(
+        &mut self,
+        canvas: &C,
+        model: &M,
+        view: &D,
+    ) -> io::Result<()> {
+        view.draw(canvas, model, self)
+    }
+
+    /// Asks for the hardware cursor to be shown once this buffer is on the screen.
+    pub fn show_cursor(&mut self, cursor: Cursor) {
+        self.cursor = Some(cursor);
+    }
+
+    pub fn hide_cursor(&mut self) {
+        self.cursor = None;
+    }
+
+    pub fn cursor(&self) -> Option<Cursor> {
+        self.cursor
+    }
+
+    /// Blanks every cell and hides the cursor.
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(Cell::reset);
+        self.cursor = None;
+    }
+
+    /// Changes the size, blanking every cell.
+    pub fn resize(&mut self, size: Size) {
+        let (w, h) = size;
+        self.size = size;
+        self.cells.clear();
+        self.cells.resize(w as usize * h as usize, Cell::default());
+    }
+
+    /// Moves every row up by `lines`, blanking the rows left at the bottom.
+    pub fn scroll_up(&mut self, lines: u16) {
+        let (w, h) = self.size;
+        let n = w as usize * lines.min(h) as usize;
+        self.cells.drain(..n);
+        self.cells.resize(w as usize * h as usize, Cell::default());
+    }
+
+    pub fn rows(&self) -> Chunks<'_, Cell> {
+        self.cells.chunks(self.size.0.max(1) as usize)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.rows()
+            .map(|row| row.iter().map(Cell::symbol).collect())
+            .collect()
+    }
+
+    /// The cells that have to be written over this buffer to turn it into `next`, in screen
+    /// order. Every cell of `next` is included if the sizes differ. Continuation cells are
+    /// left out, as writing a wide cell covers them.
+    pub fn diff<'a>(&self, next: &'a Buffer) -> Vec<(Position, &'a Cell)> {
+        let same_size = self.size == next.size;
+        next.cells
+            .iter()
+            .enumerate()
+            .filter(|&(i, cell)| !cell.is_continuation() && (!same_size || &self.cells[i] != cell))
+            .map(|(i, cell)| (next.position(i), cell))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, Cell};
+    use crate::style::{Color, Style};
+    use crate::types::*;
+    use std::io;
+
+    #[test]
+    fn it_diffs_only_changed_pixels() {
+        let previous = Buffer::new((3, 2));
+        let mut next = previous.clone();
+        next.set(&(3, 1), ('a', Style::default()));
+        next.set(&(1, 2), (' ', Style::default().fg(Color::Red)));
+        next.set(&(4, 1), ('x', Style::default()));
+
+        let diff = previous.diff(&next);
+        assert_eq!(
+            diff,
+            vec![
+                ((3, 1), &Cell::new("a", Style::default())),
+                ((1, 2), &Cell::new(" ", Style::default().fg(Color::Red))),
+            ]
+        );
+        assert!(next.diff(&next).is_empty());
+        assert_eq!(Buffer::new((2, 2)).diff(&next).len(), 6);
+    }
+
+    struct Area((u16, u16), (u16, u16));
+
+    impl Canvas for Area {
+        fn top_left_corner(&self) -> io::Result<Position> {
+            Ok(self.0)
+        }
+        fn bottom_right_corner(&self) -> io::Result<Position> {
+            Ok(self.1)
+        }
+    }
+
+    #[test]
+    fn it_draws_strings_and_boxes() {
+        let mut buffer = Buffer::new((6, 4));
+        buffer
+            .set_border(&Area((1, 1), (4, 3)), Style::default())
+            .unwrap();
+        buffer
+            .fill(&Area((2, 2), (3, 2)), ('#', Style::default()))
+            .unwrap();
+        let end = buffer.set_spans(
+            &(3, 4),
+            &[("ab", Color::Red.normal()), ("cde", Style::default())],
+        );
+
+        assert_eq!(end, (7, 4));
+        assert_eq!(buffer.lines(), vec!["┌──┐  ", "│##│  ", "└──┘  ", "  abcd"]);
+        assert_eq!(
+            buffer.get(&(3, 4)),
+            Some(&Cell::new("a", Color::Red.normal()))
+        );
+    }
+
+    #[test]
+    fn it_keeps_wide_graphemes_whole() {
+        let mut buffer = Buffer::new((5, 1));
+        let end = buffer.set_string(&(1, 1), "漢e\u{301}字", Style::default());
+
+        assert_eq!(end, (6, 1));
+        assert_eq!(buffer.lines(), vec!["漢e\u{301}字"]);
+        assert!(buffer.get(&(2, 1)).unwrap().is_continuation());
+
+        buffer.set(&(2, 1), ('x', Style::default()));
+        buffer.set(&(5, 1), ('字', Style::default()));
+        assert_eq!(buffer.lines(), vec![" xe\u{301}  "]);
+        assert_eq!(buffer.diff(&Buffer::new((5, 1))).len(), 2);
+    }
+}
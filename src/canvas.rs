@@ -84,8 +84,8 @@ mod tests {
 
     use crate::border::simple_border;
     use crate::canvas::Canvas;
+    use crate::style::Style;
     use crate::types::*;
-    use ansi_term::Style;
     use std::io;
 
     struct MyCanvas {}
@@ -0,0 +1,111 @@
+use crate::style::{ColorDepth, Style};
+use crate::sys;
+use std::env;
+use std::os::unix::io::AsRawFd;
+
+/// What the terminal can show, as far as the environment tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub color_depth: ColorDepth,
+    /// Whether colors and attributes are shown at all.
+    pub styled: bool,
+    /// Whether the output goes to a terminal rather than, say, a file or a pipe.
+    pub is_tty: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            color_depth: ColorDepth::TrueColor,
+            styled: true,
+            is_tty: true,
+        }
+    }
+}
+
+impl Capabilities {
+    /// Looks at the environment of the process and at whether `output` is a terminal.
+    pub fn detect<F: AsRawFd>(output: &F) -> Self {
+        let is_tty = sys::is_tty(output.as_raw_fd());
+        Self::from_env(|name| env::var(name).ok(), is_tty)
+    }
+
+    /// Follows the usual conventions:
+    ///
+    /// - `COLORTERM=truecolor` or `COLORTERM=24bit` means 24-bit colors, otherwise a `TERM`
+    ///   ending in `-256color` means 256 colors, and anything else gets the basic 16.
+    /// - A non-empty `NO_COLOR` turns styling off.
+    /// - Otherwise styling is on for terminals, or when `CLICOLOR_FORCE` is set to anything
+    ///   but `0`.
+    pub fn from_env<F: Fn(&str) -> Option<String>>(var: F, is_tty: bool) -> Self {
+        let is_set = |name: &str| var(name).is_some_and(|value| !value.is_empty());
+
+        let color_depth = match (var("COLORTERM").as_deref(), var("TERM")) {
+            (Some("truecolor"), _) | (Some("24bit"), _) => ColorDepth::TrueColor,
+            (_, Some(term)) if term.ends_with("-256color") => ColorDepth::Ansi256,
+            _ => ColorDepth::Ansi16,
+        };
+
+        let styled = if is_set("NO_COLOR") {
+            false
+        } else {
+            is_tty || (is_set("CLICOLOR_FORCE") && var("CLICOLOR_FORCE").as_deref() != Some("0"))
+        };
+
+        Self {
+            color_depth,
+            styled,
+            is_tty,
+        }
+    }
+
+    /// What is left of `style` on this terminal.
+    pub fn adapt(&self, style: Style) -> Style {
+        match self.styled {
+            true => style.downgrade(self.color_depth),
+            false => Style::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capabilities::Capabilities;
+    use crate::style::{Color, ColorDepth, Style};
+
+    fn detect(vars: &[(&str, &str)], is_tty: bool) -> Capabilities {
+        Capabilities::from_env(
+            |name| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            },
+            is_tty,
+        )
+    }
+
+    #[test]
+    fn it_follows_the_environment() {
+        let truecolor = detect(&[("COLORTERM", "truecolor"), ("TERM", "xterm")], true);
+        assert_eq!(truecolor.color_depth, ColorDepth::TrueColor);
+        assert!(truecolor.styled);
+
+        let xterm = detect(&[("TERM", "xterm-256color")], true);
+        assert_eq!(xterm.color_depth, ColorDepth::Ansi256);
+        assert_eq!(
+            detect(&[("TERM", "linux")], true).color_depth,
+            ColorDepth::Ansi16
+        );
+
+        assert!(!detect(&[("NO_COLOR", "1")], true).styled);
+        assert!(detect(&[("NO_COLOR", "")], true).styled);
+        assert!(!detect(&[], false).styled);
+        assert!(detect(&[("CLICOLOR_FORCE", "1")], false).styled);
+        assert!(!detect(&[("CLICOLOR_FORCE", "0")], false).styled);
+        assert!(!detect(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")], false).styled);
+
+        let orange = Color::Rgb(255, 135, 0).bold();
+        assert_eq!(xterm.adapt(orange), Color::Indexed(208).bold());
+        assert_eq!(detect(&[], false).adapt(orange), Style::default());
+    }
+}
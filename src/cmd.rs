@@ -0,0 +1,65 @@
+use std::io;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+pub(crate) enum Action<E> {
+    Perform(Box<dyn FnOnce() -> E + Send>),
+    After(Duration, E),
+    Exec(Command, Box<dyn FnOnce(io::Result<ExitStatus>) -> E + Send>),
+}
+
+/// Work to be started by the runtime after an update. Whatever it produces comes back to
+/// `update` as `Event::Msg`.
+pub struct Cmd<E> {
+    pub(crate) actions: Vec<Action<E>>,
+}
+
+impl<E> Cmd<E> {
+    pub fn none() -> Self {
+        Self {
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn batch<I: IntoIterator<Item = Cmd<E>>>(cmds: I) -> Self {
+        Self {
+            actions: cmds.into_iter().flat_map(|cmd| cmd.actions).collect(),
+        }
+    }
+
+    /// Runs `f` on a background thread and delivers its result.
+    pub fn perform<F: FnOnce() -> E + Send + 'static>(f: F) -> Self {
+        Self {
+            actions: vec![Action::Perform(Box::new(f))],
+        }
+    }
+
+    /// Delivers `msg` once `duration` has passed.
+    pub fn after(duration: Duration, msg: E) -> Self {
+        Self {
+            actions: vec![Action::After(duration, msg)],
+        }
+    }
+
+    /// Hands the terminal over to `command` until it exits, e.g. to open `$EDITOR`, then
+    /// delivers what `f` makes of its exit status. The app is redrawn afterwards. The command
+    /// reads from and writes to the terminal even if our own standard streams are redirected.
+    pub fn exec<F: FnOnce(io::Result<ExitStatus>) -> E + Send + 'static>(
+        command: Command,
+        f: F,
+    ) -> Self {
+        Self {
+            actions: vec![Action::Exec(command, Box::new(f))],
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl<E> Default for Cmd<E> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
@@ -0,0 +1,73 @@
+use crate::backend::Waker;
+use crate::capabilities::Capabilities;
+use std::sync::mpsc::{Receiver, SendError, Sender};
+use std::thread;
+
+/// A cloneable handle for sending messages to a running app from other threads. Every message
+/// wakes the app up and arrives in `update` as `Event::Msg`.
+pub struct AppHandle<E> {
+    sender: Sender<E>,
+    waker: Waker,
+}
+
+impl<E> Clone for AppHandle<E> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            waker: self.waker.clone(),
+        }
+    }
+}
+
+impl<E: Send + 'static> AppHandle<E> {
+    pub(crate) fn new(sender: Sender<E>, waker: Waker) -> Self {
+        Self { sender, waker }
+    }
+
+    /// Fails once the app has stopped.
+    pub fn send(&self, msg: E) -> Result<(), SendError<E>> {
+        self.sender.send(msg)?;
+        self.waker.wake();
+        Ok(())
+    }
+
+    /// Forwards every message from `receiver` to the app until either side hangs up.
+    pub fn subscribe(&self, receiver: Receiver<E>) {
+        let handle = self.clone();
+        thread::spawn(move || {
+            for msg in receiver {
+                if handle.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// What `init` gets to know about the app it is starting.
+pub struct Context<E> {
+    handle: AppHandle<E>,
+    capabilities: Capabilities,
+}
+
+impl<E: Send + 'static> Context<E> {
+    pub(crate) fn new(handle: AppHandle<E>, capabilities: Capabilities) -> Self {
+        Self {
+            handle,
+            capabilities,
+        }
+    }
+
+    /// What the terminal can show, e.g. to pick a color scheme.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    pub fn handle(&self) -> AppHandle<E> {
+        self.handle.clone()
+    }
+
+    pub fn subscribe(&self, receiver: Receiver<E>) {
+        self.handle.subscribe(receiver)
+    }
+}
@@ -0,0 +1,51 @@
+use crate::types::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar,
+}
+
+/// The hardware cursor, as requested by a view with `Buffer::show_cursor`. Without a
+/// request the cursor stays hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub position: Position,
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl Cursor {
+    /// A blinking block, which is what most terminals show by default.
+    pub fn new(position: Position) -> Self {
+        Self {
+            position,
+            shape: CursorShape::Block,
+            blinking: true,
+        }
+    }
+
+    pub fn shape(mut self, shape: CursorShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn steady(mut self) -> Self {
+        self.blinking = false;
+        self
+    }
+}
+
+/// The DECSCUSR sequence selecting a cursor shape.
+pub(crate) fn shape_sequence(shape: CursorShape, blinking: bool) -> String {
+    let n = match shape {
+        CursorShape::Block => 1,
+        CursorShape::Underline => 3,
+        CursorShape::Bar => 5,
+    } + !blinking as u8;
+    format!("\x1b[{} q", n)
+}
+
+/// Brings back the shape the user has configured.
+pub(crate) const RESET_SHAPE_SEQUENCE: &str = "\x1b[0 q";
@@ -1,10 +1,159 @@
-pub use termion::event::{Event as Input, Key, MouseEvent as Mouse};
+use crate::types::Size;
+use std::ops::{BitOr, BitOrAssign};
+use std::time::{Duration, Instant};
+pub use termion::event::MouseEvent as Mouse;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    /// A key that produces text. Letters typed with Shift come in upper case.
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Esc,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    F(u8),
+}
+
+/// The modifier keys held down with a key. Legacy terminals don't report all of them, e.g.
+/// Shift with Ctrl and a letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Self = Self(0);
+    pub const SHIFT: Self = Self(1);
+    pub const ALT: Self = Self(2);
+    pub const CTRL: Self = Self(4);
+    pub const SUPER: Self = Self(8);
+
+    /// Turns the `1 + bits` encoding used by xterm and kitty into modifiers, ignoring those
+    /// we don't know about.
+    pub(crate) fn from_parameter(parameter: u32) -> Self {
+        Self((parameter.saturating_sub(1) & 0b1111) as u8)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Repeats and releases are only reported with `Keyboard::EnhancedWithReleases`, and only by
+/// terminals that support it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyEventKind {
+    #[default]
+    Press,
+    Repeat,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub modifiers: Modifiers,
+    pub kind: KeyEventKind,
+}
+
+impl KeyEvent {
+    pub fn new(code: KeyCode, modifiers: Modifiers) -> Self {
+        Self {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+        }
+    }
+
+    /// Whether this presses or repeats `binding`, with exactly the same modifiers.
+    pub fn matches(&self, binding: &KeyEvent) -> bool {
+        self.kind != KeyEventKind::Release
+            && self.code == binding.code
+            && self.modifiers == binding.modifiers
+    }
+}
+
+impl From<KeyCode> for KeyEvent {
+    fn from(code: KeyCode) -> Self {
+        Self::new(code, Modifiers::NONE)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Input {
+    Key(KeyEvent),
+    Mouse(Mouse),
+}
+
+/// What a backend reads from the terminal. Pastes and focus changes are delivered as events of
+/// their own rather than as `Event::Input`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TerminalEvent {
+    Input(Input),
+    /// Text pasted while bracketed paste was on, with newlines as `\n`.
+    Paste(String),
+    FocusGained,
+    FocusLost,
+}
+
+impl From<Input> for TerminalEvent {
+    fn from(input: Input) -> Self {
+        Self::Input(input)
+    }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Event<T> {
     NoOp,
     Input(Input),
+    /// Pasted text, delivered at once rather than key by key.
+    Paste(String),
+    /// Only delivered if `Options::focus_events` is on.
+    FocusGained,
+    FocusLost,
     Msg(T),
+    Resize(Size),
+    Tick {
+        now: Instant,
+        elapsed: Duration,
+    },
     GracefulStop,
     Stop,
 }
+
+impl<T> From<TerminalEvent> for Event<T> {
+    fn from(input: TerminalEvent) -> Self {
+        match input {
+            TerminalEvent::Input(input) => Event::Input(input),
+            TerminalEvent::Paste(text) => Event::Paste(text),
+            TerminalEvent::FocusGained => Event::FocusGained,
+            TerminalEvent::FocusLost => Event::FocusLost,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ExitCode {
     OK = 0,
     GeneralFailiure = 1,
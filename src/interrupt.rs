@@ -0,0 +1,151 @@
+use crate::backend::Waker;
+use crate::event::{KeyCode, KeyEvent, Modifiers};
+use crate::sys::{self, SigAction};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::{Handle, Signals};
+use std::io;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Decides what asks a running app to stop. The first request is delivered to `update` as
+/// `Event::GracefulStop`, after which the app stops. With a timeout, the app keeps running until
+/// it returns `Event::Stop` instead; a second request, or the timeout running out, stops it
+/// forcefully.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interrupt {
+    /// Keys that request a stop. Keys not listed here, Ctrl-C included, arrive as plain input.
+    pub keys: Vec<KeyEvent>,
+    /// Signals that request a stop, such as `SIGINT`, `SIGTERM` or `SIGHUP`.
+    pub signals: Vec<c_int>,
+    /// How long the app may take to stop by itself after `Event::GracefulStop`, before the
+    /// stop becomes a forceful one.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Interrupt {
+    fn default() -> Self {
+        Self {
+            keys: vec![KeyEvent::new(KeyCode::Char('c'), Modifiers::CTRL)],
+            signals: vec![SIGINT, SIGTERM, SIGHUP],
+            timeout: None,
+        }
+    }
+}
+
+impl Interrupt {
+    /// Only lets the app stop itself: no keys or signals are intercepted.
+    pub fn none() -> Self {
+        Self {
+            keys: Vec::new(),
+            signals: Vec::new(),
+            timeout: None,
+        }
+    }
+}
+
+pub(crate) enum Request {
+    Graceful,
+    Forceful,
+}
+
+/// Tracks stop requests while the app is running.
+pub(crate) struct Interrupter {
+    keys: Vec<KeyEvent>,
+    timeout: Option<Duration>,
+    signaled: Arc<AtomicBool>,
+    signals: Option<Handle>,
+    /// What handled the signals before, to be restored once we are done.
+    previous: Vec<(c_int, SigAction)>,
+    requested_at: Option<Instant>,
+}
+
+impl Interrupter {
+    pub fn new(policy: &Interrupt, waker: Waker) -> io::Result<Self> {
+        let signaled = Arc::new(AtomicBool::new(false));
+        let previous = policy
+            .signals
+            .iter()
+            .map(|&signal| Ok((signal, sys::claim_signal(signal)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let signals = match policy.signals.is_empty() {
+            true => None,
+            false => {
+                let mut signals = Signals::new(&policy.signals)?;
+                let handle = signals.handle();
+                let signaled = signaled.clone();
+                thread::spawn(move || {
+                    for _ in signals.forever() {
+                        signaled.store(true, Ordering::SeqCst);
+                        waker.wake();
+                    }
+                });
+                Some(handle)
+            }
+        };
+
+        Ok(Self {
+            keys: policy.keys.clone(),
+            timeout: policy.timeout,
+            signaled,
+            signals,
+            previous,
+            requested_at: None,
+        })
+    }
+
+    pub fn is_interrupt_key(&self, key: &KeyEvent) -> bool {
+        self.keys.iter().any(|binding| key.matches(binding))
+    }
+
+    /// Registers a stop request from a key or a signal.
+    pub fn request(&mut self, now: Instant) -> Request {
+        match self.requested_at {
+            Some(_) => Request::Forceful,
+            None => {
+                self.requested_at = Some(now);
+                Request::Graceful
+            }
+        }
+    }
+
+    /// Checks for signals and an expired graceful stop.
+    pub fn poll(&mut self, now: Instant) -> Option<Request> {
+        if self.signaled.swap(false, Ordering::SeqCst) {
+            return Some(self.request(now));
+        }
+
+        match self.deadline() {
+            Some(deadline) if now >= deadline => Some(Request::Forceful),
+            _ => None,
+        }
+    }
+
+    /// Forgets signals that arrived while a child process had the terminal: the terminal sends
+    /// `SIGINT` to the whole foreground process group, so a Ctrl-C meant for the child reaches
+    /// us as well.
+    pub fn discard(&mut self) {
+        self.signaled.store(false, Ordering::SeqCst);
+    }
+
+    /// When a pending graceful stop turns into a forceful one.
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.requested_at, self.timeout) {
+            (Some(requested_at), Some(timeout)) => Some(requested_at + timeout),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Interrupter {
+    fn drop(&mut self) {
+        if let Some(handle) = self.signals.take() {
+            handle.close();
+        }
+        for (signal, previous) in &self.previous {
+            let _ = sys::release_signal(*signal, previous);
+        }
+    }
+}
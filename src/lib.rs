@@ -2,9 +2,23 @@ pub extern crate ansi_term;
 pub extern crate termion;
 
 pub mod app;
+pub mod backend;
+pub mod border;
+pub mod buffer;
 pub mod canvas;
+pub mod capabilities;
+pub mod cmd;
+pub mod context;
+pub mod cursor;
 pub mod event;
 pub mod exit_code;
+pub mod interrupt;
+mod parser;
+mod scheduler;
+pub mod style;
+mod suspend;
+mod sys;
+pub mod testing;
 pub mod types;
 
 // pub mod prelude;
@@ -0,0 +1,645 @@
+use crate::event::{Input, KeyCode, KeyEvent, KeyEventKind, Modifiers, TerminalEvent};
+use crate::types::Position;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use termion::event::{MouseButton, MouseEvent};
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Parses raw bytes read from the terminal into inputs. What can't be parsed yet is left in
+/// `bytes`: a paste that has not ended, or an escape sequence that was cut short, which may
+/// also be a lone Esc key press, see `flush`. Bytes that don't form a known sequence are
+/// dropped. Replies to queries outside of pastes are dropped, in case they arrive after we
+/// stopped waiting for them.
+pub fn parse(bytes: &mut Vec<u8>, inputs: &mut VecDeque<TerminalEvent>) {
+    take_replies(bytes);
+    let mut rest = &bytes[..];
+
+    while !rest.is_empty() {
+        if let Some(text) = rest.strip_prefix(PASTE_START) {
+            let end = match find(text, PASTE_END) {
+                Some(end) => end,
+                None => break,
+            };
+            let text = String::from_utf8_lossy(&text[..end]);
+            inputs.push_back(TerminalEvent::Paste(
+                text.replace("\r\n", "\n").replace('\r', "\n"),
+            ));
+            rest = &rest[PASTE_START.len() + end + PASTE_END.len()..];
+            continue;
+        }
+
+        match next(rest) {
+            Token::Input(input, len) => {
+                inputs.push_back(input.into());
+                rest = &rest[len..];
+            }
+            Token::Focus(gained, len) => {
+                inputs.push_back(match gained {
+                    true => TerminalEvent::FocusGained,
+                    false => TerminalEvent::FocusLost,
+                });
+                rest = &rest[len..];
+            }
+            Token::Skip(len) => rest = &rest[len..],
+            Token::Incomplete => break,
+        }
+    }
+
+    *bytes = rest.to_vec();
+}
+
+/// Whether `parse` left something other than an unfinished paste, which would be worth
+/// flushing if nothing else arrives.
+pub fn is_incomplete(bytes: &[u8]) -> bool {
+    !bytes.is_empty() && !bytes.starts_with(PASTE_START)
+}
+
+/// Parses what `parse` left, once the terminal had its chance to send the rest: an escape byte
+/// that starts no complete sequence is the Esc key.
+pub fn flush(bytes: &mut Vec<u8>, inputs: &mut VecDeque<TerminalEvent>) {
+    while is_incomplete(bytes) {
+        if bytes[0] == b'\x1b' {
+            inputs.push_back(Input::Key(KeyCode::Esc.into()).into());
+        }
+        bytes.remove(0);
+        parse(bytes, inputs);
+    }
+}
+
+fn find(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    bytes
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The length of the paste at the start of `bytes`, markers included, or of all of `bytes` if
+/// the paste has not ended yet.
+fn paste_len(bytes: &[u8]) -> Option<usize> {
+    let text = bytes.strip_prefix(PASTE_START)?;
+    Some(match find(text, PASTE_END) {
+        Some(end) => PASTE_START.len() + end + PASTE_END.len(),
+        None => bytes.len(),
+    })
+}
+
+/// What was found at the start of the bytes read.
+enum Token {
+    Input(Input, usize),
+    /// A focus report, whether the focus was gained or lost.
+    Focus(bool, usize),
+    /// A sequence we don't know about, to be dropped.
+    Skip(usize),
+    Incomplete,
+}
+
+fn key(code: KeyCode, modifiers: Modifiers, len: usize) -> Token {
+    Token::Input(Input::Key(KeyEvent::new(code, modifiers)), len)
+}
+
+fn next(bytes: &[u8]) -> Token {
+    match bytes {
+        [b'\x1b'] | [b'\x1b', b'O'] => Token::Incomplete,
+        [b'\x1b', b'[', ..] => csi(bytes),
+        [b'\x1b', b'O', byte, ..] => ss3(*byte),
+        // Alt is sent as an escape byte before the key.
+        [b'\x1b', rest @ ..] => match next(rest) {
+            Token::Input(Input::Key(mut key), len) => {
+                key.modifiers |= Modifiers::ALT;
+                Token::Input(Input::Key(key), len + 1)
+            }
+            Token::Incomplete => Token::Incomplete,
+            _ => key(KeyCode::Esc, Modifiers::NONE, 1),
+        },
+        _ => plain(bytes),
+    }
+}
+
+/// A single key that isn't an escape sequence: a control character or UTF-8 text.
+fn plain(bytes: &[u8]) -> Token {
+    let ctrl = |c: u8| key(KeyCode::Char(c as char), Modifiers::CTRL, 1);
+    match bytes[0] {
+        b'\r' | b'\n' => key(KeyCode::Enter, Modifiers::NONE, 1),
+        b'\t' => key(KeyCode::Tab, Modifiers::NONE, 1),
+        b'\x08' | b'\x7f' => key(KeyCode::Backspace, Modifiers::NONE, 1),
+        0 => ctrl(b' '),
+        byte @ 1..=26 => ctrl(b'a' + byte - 1),
+        byte @ 28..=31 => ctrl(b'\\' + byte - 28),
+        byte => {
+            let len = match byte {
+                0x00..=0x7f => 1,
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => return Token::Skip(1),
+            };
+            if bytes.len() < len {
+                return Token::Incomplete;
+            }
+            match std::str::from_utf8(&bytes[..len])
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                Some(c) => key(KeyCode::Char(c), shift_for(c), len),
+                None => Token::Skip(1),
+            }
+        }
+    }
+}
+
+/// Legacy terminals send upper case letters without saying Shift was held.
+fn shift_for(c: char) -> Modifiers {
+    match c.is_uppercase() {
+        true => Modifiers::SHIFT,
+        false => Modifiers::NONE,
+    }
+}
+
+/// Keys sent as `ESC O` and a letter, as some terminals do in application cursor mode.
+fn ss3(byte: u8) -> Token {
+    match legacy_code(byte) {
+        Some(code) => key(code, Modifiers::NONE, 3),
+        None => Token::Skip(3),
+    }
+}
+
+/// The keys sent as `CSI 1 ; modifiers` and a letter, or `SS3` and a letter.
+fn legacy_code(byte: u8) -> Option<KeyCode> {
+    Some(match byte {
+        b'A' => KeyCode::Up,
+        b'B' => KeyCode::Down,
+        b'C' => KeyCode::Right,
+        b'D' => KeyCode::Left,
+        b'H' => KeyCode::Home,
+        b'F' => KeyCode::End,
+        b'P' => KeyCode::F(1),
+        b'Q' => KeyCode::F(2),
+        b'R' => KeyCode::F(3),
+        b'S' => KeyCode::F(4),
+        _ => return None,
+    })
+}
+
+/// Control sequences: keys in the legacy xterm encoding or the kitty keyboard protocol, mouse
+/// reports and focus reports.
+fn csi(bytes: &[u8]) -> Token {
+    if bytes.starts_with(b"\x1b[M") {
+        return match bytes.len() {
+            0..=5 => Token::Incomplete,
+            _ => x10_mouse(&bytes[..6]),
+        };
+    }
+
+    let end = match bytes[2..].iter().position(|b| !(0x20..=0x3f).contains(b)) {
+        Some(i) => i + 2,
+        None => return Token::Incomplete,
+    };
+    if !(0x40..=0x7e).contains(&bytes[end]) {
+        return Token::Skip(end);
+    }
+    let len = end + 1;
+    let params = &bytes[2..end];
+
+    match (params.first(), bytes[end]) {
+        (Some(b'<'), b'M' | b'm') => {
+            let report = mouse_numbers(&params[1..]);
+            return mouse(report.map(|(b, x, y)| (b, x, y, bytes[end] == b'm')), len);
+        }
+        (Some(_), b'M') => {
+            // urxvt offsets the button by 32, like X10 does.
+            let report = mouse_numbers(params);
+            let report = report.and_then(|(b, x, y)| Some((b.checked_sub(32)?, x, y, false)));
+            return mouse(report, len);
+        }
+        (None, b'I') => return Token::Focus(true, len),
+        (None, b'O') => return Token::Focus(false, len),
+        _ => {}
+    }
+
+    // Parameters are separated by `;`, and may have sub-parameters separated by `:`.
+    let numbers = match std::str::from_utf8(params).ok().and_then(numbers) {
+        Some(numbers) => numbers,
+        None => return Token::Skip(len),
+    };
+    let number = |i: usize, j: usize| numbers.get(i).and_then(|n| n.get(j)).copied().flatten();
+
+    let mut modifiers = Modifiers::from_parameter(number(1, 0).unwrap_or(1));
+    let kind = match number(1, 1) {
+        Some(2) => KeyEventKind::Repeat,
+        Some(3) => KeyEventKind::Release,
+        _ => KeyEventKind::Press,
+    };
+    let code = match (bytes[end], number(0, 0)) {
+        (b'Z', _) => {
+            modifiers |= Modifiers::SHIFT;
+            Some(KeyCode::Tab)
+        }
+        (b'~', Some(n)) => tilde_code(n),
+        (b'u', Some(n)) => kitty_code(n, number(0, 1), &mut modifiers),
+        (byte, _) => legacy_code(byte),
+    };
+
+    match code {
+        Some(code) => Token::Input(
+            Input::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+            }),
+            len,
+        ),
+        None => Token::Skip(len),
+    }
+}
+
+fn numbers(params: &str) -> Option<Vec<Vec<Option<u32>>>> {
+    params
+        .split(';')
+        .map(|param| {
+            param
+                .split(':')
+                .map(|n| match n {
+                    "" => Some(None),
+                    n => n.parse().ok().map(Some),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The keys sent as `CSI number ; modifiers ~`.
+fn tilde_code(number: u32) -> Option<KeyCode> {
+    Some(match number {
+        1 | 7 => KeyCode::Home,
+        2 => KeyCode::Insert,
+        3 => KeyCode::Delete,
+        4 | 8 => KeyCode::End,
+        5 => KeyCode::PageUp,
+        6 => KeyCode::PageDown,
+        11..=15 => KeyCode::F((number - 10) as u8),
+        17..=21 => KeyCode::F((number - 11) as u8),
+        23..=24 => KeyCode::F((number - 12) as u8),
+        _ => return None,
+    })
+}
+
+/// The keys sent as `CSI code : shifted code ; modifiers u` by the kitty keyboard protocol.
+/// Functional keys without a legacy encoding use codes from the private use area.
+fn kitty_code(code: u32, shifted: Option<u32>, modifiers: &mut Modifiers) -> Option<KeyCode> {
+    let keypad = |c: char| Some(KeyCode::Char(c));
+    match code {
+        9 => Some(KeyCode::Tab),
+        13 | 57414 => Some(KeyCode::Enter),
+        27 => Some(KeyCode::Esc),
+        8 | 127 => Some(KeyCode::Backspace),
+        57376..=57398 => Some(KeyCode::F((code - 57376 + 13) as u8)),
+        57399..=57408 => keypad(char::from_digit(code - 57399, 10)?),
+        57409 => keypad('.'),
+        57410 => keypad('/'),
+        57411 => keypad('*'),
+        57412 => keypad('-'),
+        57413 => keypad('+'),
+        57415 => keypad('='),
+        57417 => Some(KeyCode::Left),
+        57418 => Some(KeyCode::Right),
+        57419 => Some(KeyCode::Up),
+        57420 => Some(KeyCode::Down),
+        57421 => Some(KeyCode::PageUp),
+        57422 => Some(KeyCode::PageDown),
+        57423 => Some(KeyCode::Home),
+        57424 => Some(KeyCode::End),
+        57425 => Some(KeyCode::Insert),
+        57426 => Some(KeyCode::Delete),
+        // Modifier keys on their own, media keys and the like.
+        57344..=63743 => None,
+        _ => {
+            let shift = modifiers.contains(Modifiers::SHIFT);
+            let c = match shifted.filter(|_| shift).and_then(char::from_u32) {
+                Some(c) => c,
+                None => char::from_u32(code)?,
+            };
+            let mut upper = c.to_uppercase();
+            match (shift, upper.next(), upper.next()) {
+                (true, Some(upper), None) => Some(KeyCode::Char(upper)),
+                _ => Some(KeyCode::Char(c)),
+            }
+        }
+    }
+}
+
+/// Mouse reports in the X10 encoding, `CSI M button x y`, with every number offset by 32 and sent
+/// as a single byte.
+fn x10_mouse(sequence: &[u8]) -> Token {
+    let number = |i: usize| sequence[i].checked_sub(32).map(u16::from);
+    let report = match (number(3), number(4), number(5)) {
+        (Some(b), Some(x), Some(y)) => Some((b, x, y, false)),
+        _ => None,
+    };
+    mouse(report, sequence.len())
+}
+
+/// The button, column and row of an SGR (`CSI < b ; x ; y M`) or urxvt (`CSI b ; x ; y M`) report.
+fn mouse_numbers(params: &[u8]) -> Option<(u16, u16, u16)> {
+    let numbers = numbers(std::str::from_utf8(params).ok()?)?;
+    let number = |n: &[Option<u32>]| match *n {
+        [Some(n)] => u16::try_from(n).ok(),
+        _ => None,
+    };
+    match &numbers[..] {
+        [b, x, y] => Some((number(b)?, number(x)?, number(y)?)),
+        _ => None,
+    }
+}
+
+/// Turns a report of `(button, x, y, released)` into a mouse event, skipping `len` bytes when it
+/// is malformed or describes something termion's events can't.
+fn mouse(report: Option<(u16, u16, u16, bool)>, len: usize) -> Token {
+    let (b, x, y, released) = match report {
+        Some(report) => report,
+        None => return Token::Skip(len),
+    };
+    // Bits 2 to 4 are the modifiers held, which termion's events don't carry.
+    let button = match b & !0b1_1100 {
+        0 => MouseButton::Left,
+        1 => MouseButton::Middle,
+        2 => MouseButton::Right,
+        3 => return Token::Input(Input::Mouse(MouseEvent::Release(x, y)), len),
+        32..=35 => return Token::Input(Input::Mouse(MouseEvent::Hold(x, y)), len),
+        64 => MouseButton::WheelUp,
+        65 => MouseButton::WheelDown,
+        _ => return Token::Skip(len),
+    };
+    let mouse = match released {
+        true => MouseEvent::Release(x, y),
+        false => MouseEvent::Press(button, x, y),
+    };
+    Token::Input(Input::Mouse(mouse), len)
+}
+
+/// Replies to the queries a backend sends to the terminal.
+#[derive(Debug, Default, PartialEq)]
+pub struct Replies {
+    /// Modes and their states as reported by DECRPM, `CSI ? mode ; state $ y`.
+    pub modes: Vec<(u16, u16)>,
+    /// The flags of the kitty keyboard protocol, `CSI ? flags u`, from terminals that have it.
+    pub keyboard_flags: Option<u16>,
+    /// Whether the primary device attributes, `CSI ? ... c`, were reported.
+    pub device_attributes: bool,
+}
+
+/// Cuts the replies out of `bytes`, leaving everything else, such as keys pressed or text
+/// pasted meanwhile, in place.
+pub fn take_replies(bytes: &mut Vec<u8>) -> Replies {
+    let mut replies = Replies::default();
+    let mut rest = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if let Some(len) = paste_len(&bytes[i..]) {
+            rest.extend_from_slice(&bytes[i..i + len]);
+            i += len;
+            continue;
+        }
+
+        match reply(&bytes[i..]) {
+            Some((len, numbers, b'y')) => {
+                if let [mode, state] = numbers[..] {
+                    replies.modes.push((mode, state));
+                }
+                i += len;
+            }
+            Some((len, numbers, b'u')) => {
+                replies.keyboard_flags = numbers.first().copied().or(Some(0));
+                i += len;
+            }
+            Some((len, _, _)) => {
+                replies.device_attributes = true;
+                i += len;
+            }
+            None => {
+                rest.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    *bytes = rest;
+    replies
+}
+
+/// Cuts a cursor position report, `CSI row ; column R`, out of `bytes`, leaving pastes alone.
+/// It looks like F3 with modifiers, so it is only looked for while waiting for one.
+pub fn take_cursor_position(bytes: &mut Vec<u8>) -> Option<Position> {
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(len) = paste_len(&bytes[i..]) {
+            i += len;
+            continue;
+        }
+        if let Some((len, position)) = cursor_position(&bytes[i..]) {
+            bytes.drain(i..i + len);
+            return Some(position);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Recognizes a cursor position report at the start of `bytes`, returning its length and the
+/// position.
+fn cursor_position(bytes: &[u8]) -> Option<(usize, Position)> {
+    let params = bytes.strip_prefix(b"\x1b[")?;
+    let end = params
+        .iter()
+        .position(|b| !(b.is_ascii_digit() || *b == b';'))?;
+    if params[end] != b'R' {
+        return None;
+    }
+    let (row, column) = std::str::from_utf8(&params[..end]).ok()?.split_once(';')?;
+    Some((2 + end + 1, (column.parse().ok()?, row.parse().ok()?)))
+}
+
+/// Recognizes a DECRPM, keyboard flags or device attributes reply at the start of `bytes`, returning its length,
+/// its parameters and its final byte.
+fn reply(bytes: &[u8]) -> Option<(usize, Vec<u16>, u8)> {
+    let params = bytes.strip_prefix(b"\x1b[?")?;
+    let end = params
+        .iter()
+        .position(|b| !(b.is_ascii_digit() || *b == b';'))?;
+    let numbers = match end {
+        0 => Vec::new(),
+        _ => params[..end]
+            .split(|&b| b == b';')
+            .map(|n| std::str::from_utf8(n).ok()?.parse().ok())
+            .collect::<Option<Vec<u16>>>()?,
+    };
+
+    match params[end..] {
+        [b'$', b'y', ..] => Some((3 + end + 2, numbers, b'y')),
+        [b'u', ..] => Some((3 + end + 1, numbers, b'u')),
+        [b'c', ..] => Some((3 + end + 1, numbers, b'c')),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event::{Input, KeyCode, KeyEvent, KeyEventKind, Modifiers, TerminalEvent};
+    use crate::parser::{flush, parse, take_cursor_position, take_replies};
+    use std::collections::VecDeque;
+    use termion::event::{MouseButton, MouseEvent};
+
+    fn key(code: KeyCode, modifiers: Modifiers) -> TerminalEvent {
+        Input::Key(KeyEvent::new(code, modifiers)).into()
+    }
+
+    fn char(c: char) -> TerminalEvent {
+        key(KeyCode::Char(c), Modifiers::NONE)
+    }
+
+    fn parse_all(bytes: &[u8]) -> Vec<TerminalEvent> {
+        let mut inputs = VecDeque::new();
+        let mut bytes = bytes.to_vec();
+        parse(&mut bytes, &mut inputs);
+        flush(&mut bytes, &mut inputs);
+        inputs.into()
+    }
+
+    #[test]
+    fn it_takes_replies_out_of_input() {
+        let mut bytes = b"a\x1b[?2026;2$yb\x1b[?1uc\x1b[?62;22cd".to_vec();
+        let replies = take_replies(&mut bytes);
+
+        assert_eq!(replies.modes, vec![(2026, 2)]);
+        assert_eq!(replies.keyboard_flags, Some(1));
+        assert!(replies.device_attributes);
+        assert_eq!(bytes, b"abcd");
+    }
+
+    #[test]
+    fn it_leaves_replies_in_pastes_alone() {
+        let mut bytes = b"\x1b[200~\x1b[?1u\x1b[201~\x1b[?1u\x1b[200~\x1b[?62c".to_vec();
+        let replies = take_replies(&mut bytes);
+
+        assert_eq!(replies.keyboard_flags, Some(1));
+        assert!(!replies.device_attributes);
+        assert_eq!(bytes, b"\x1b[200~\x1b[?1u\x1b[201~\x1b[200~\x1b[?62c");
+    }
+
+    #[test]
+    fn it_takes_the_cursor_position_out_of_input() {
+        let mut bytes = b"a\x1b[1;2Pb\x1b[12;3Rc".to_vec();
+
+        assert_eq!(take_cursor_position(&mut bytes), Some((3, 12)));
+        assert_eq!(bytes, b"a\x1b[1;2Pbc");
+        assert_eq!(take_cursor_position(&mut bytes), None);
+
+        let mut bytes = b"\x1b[200~\x1b[1;2R\x1b[201~".to_vec();
+        assert_eq!(take_cursor_position(&mut bytes), None);
+    }
+
+    #[test]
+    fn it_parses_legacy_keys() {
+        assert_eq!(
+            parse_all(b"aA\x03\t\r\x7f\x1bx\x1b[1;6C\x1b[Z\x1b[15;3~\x1bOP\xc3\xa9"),
+            vec![
+                char('a'),
+                key(KeyCode::Char('A'), Modifiers::SHIFT),
+                key(KeyCode::Char('c'), Modifiers::CTRL),
+                key(KeyCode::Tab, Modifiers::NONE),
+                key(KeyCode::Enter, Modifiers::NONE),
+                key(KeyCode::Backspace, Modifiers::NONE),
+                key(KeyCode::Char('x'), Modifiers::ALT),
+                key(KeyCode::Right, Modifiers::CTRL | Modifiers::SHIFT),
+                key(KeyCode::Tab, Modifiers::SHIFT),
+                key(KeyCode::F(5), Modifiers::ALT),
+                key(KeyCode::F(1), Modifiers::NONE),
+                char('é'),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_waits_for_the_rest_of_an_escape_sequence() {
+        let mut inputs = VecDeque::new();
+        let mut bytes = b"a\x1b".to_vec();
+        parse(&mut bytes, &mut inputs);
+        assert_eq!(inputs, vec![char('a')]);
+
+        bytes.extend(b"[A\x1b[1;5");
+        parse(&mut bytes, &mut inputs);
+        assert_eq!(inputs, vec![char('a'), key(KeyCode::Up, Modifiers::NONE)]);
+        assert_eq!(bytes, b"\x1b[1;5");
+
+        bytes = b"\x1b".to_vec();
+        flush(&mut bytes, &mut inputs);
+        assert_eq!(inputs.pop_back(), Some(key(KeyCode::Esc, Modifiers::NONE)));
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn it_parses_the_kitty_keyboard_protocol() {
+        let release = KeyEvent {
+            kind: KeyEventKind::Release,
+            ..KeyEvent::new(KeyCode::Char('a'), Modifiers::NONE)
+        };
+        assert_eq!(
+            parse_all(
+                b"\x1b[99;5u\x1b[105;5u\x1b[27u\x1b[97:65;2u\x1b[97;1:3u\x1b[57441;2u\x1b[3;9~"
+            ),
+            vec![
+                key(KeyCode::Char('c'), Modifiers::CTRL),
+                key(KeyCode::Char('i'), Modifiers::CTRL),
+                key(KeyCode::Esc, Modifiers::NONE),
+                key(KeyCode::Char('A'), Modifiers::SHIFT),
+                Input::Key(release).into(),
+                key(KeyCode::Delete, Modifiers::SUPER),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_delivers_pastes_whole() {
+        let mut inputs = VecDeque::new();
+        let mut bytes = b"a\x1b[200~b\rc".to_vec();
+        parse(&mut bytes, &mut inputs);
+        assert_eq!(inputs, vec![char('a')]);
+
+        bytes.extend(b"\r\nd\x1b[201~e");
+        parse(&mut bytes, &mut inputs);
+        assert_eq!(
+            inputs,
+            vec![
+                char('a'),
+                TerminalEvent::Paste("b\nc\nd".to_string()),
+                char('e')
+            ]
+        );
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn it_reports_focus_changes_and_the_mouse() {
+        assert!(matches!(
+            parse_all(b"\x1b[Oa\x1b[I\x1b[<0;3;2M")[..],
+            [
+                TerminalEvent::FocusLost,
+                _,
+                TerminalEvent::FocusGained,
+                TerminalEvent::Input(Input::Mouse(_))
+            ]
+        ));
+        assert_eq!(
+            parse_all(b"\x1b[<0;3;2m\x1b[M#!\"\x1b[<64;1;1M\x1b[34;4;5M"),
+            [
+                TerminalEvent::Input(Input::Mouse(MouseEvent::Release(3, 2))),
+                TerminalEvent::Input(Input::Mouse(MouseEvent::Release(1, 2))),
+                TerminalEvent::Input(Input::Mouse(MouseEvent::Press(MouseButton::WheelUp, 1, 1))),
+                TerminalEvent::Input(Input::Mouse(MouseEvent::Press(MouseButton::Right, 4, 5))),
+            ]
+        );
+        // Malformed reports are skipped rather than taken for keys.
+        assert_eq!(parse_all(b"\x1b[<0;3M\x1b[<M\x1b[1;5Ma"), [char('a')]);
+    }
+}
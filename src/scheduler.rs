@@ -0,0 +1,162 @@
+use crate::backend::Waker;
+use crate::cmd::{Action, Cmd};
+use crate::context::AppHandle;
+use crate::event::Event;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Command, ExitStatus};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub type Exec<E> = (Command, Box<dyn FnOnce(io::Result<ExitStatus>) -> E + Send>);
+
+/// Keeps track of everything besides terminal input that produces events: ticks, timers, the
+/// results of commands running on other threads and messages sent through `AppHandle`s.
+/// Commands that panic send back their panic message instead.
+pub struct Scheduler<E> {
+    tick_rate: Option<Duration>,
+    last_tick: Instant,
+    timers: Vec<(Instant, E)>,
+    execs: VecDeque<Exec<E>>,
+    sender: Sender<Result<E, String>>,
+    receiver: Receiver<Result<E, String>>,
+    received: VecDeque<Result<E, String>>,
+    handle: AppHandle<E>,
+    messages: Receiver<E>,
+    in_flight: usize,
+    waker: Waker,
+}
+
+impl<E: Send + 'static> Scheduler<E> {
+    pub fn new(tick_rate: Option<Duration>, waker: Waker) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let (handle, messages) = mpsc::channel();
+        Self {
+            tick_rate,
+            last_tick: Instant::now(),
+            timers: Vec::new(),
+            execs: VecDeque::new(),
+            sender,
+            receiver,
+            received: VecDeque::new(),
+            handle: AppHandle::new(handle, waker.clone()),
+            messages,
+            in_flight: 0,
+            waker,
+        }
+    }
+
+    pub fn handle(&self) -> AppHandle<E> {
+        self.handle.clone()
+    }
+
+    pub fn execute(&mut self, cmd: Cmd<E>) {
+        for action in cmd.actions {
+            match action {
+                Action::Perform(f) => {
+                    let sender = self.sender.clone();
+                    let waker = self.waker.clone();
+                    self.in_flight += 1;
+                    thread::spawn(move || {
+                        let result = panic::catch_unwind(AssertUnwindSafe(f));
+                        let _ = sender.send(result.map_err(|payload| panic_message(&*payload)));
+                        waker.wake();
+                    });
+                }
+                Action::After(duration, msg) => {
+                    self.timers.push((Instant::now() + duration, msg));
+                }
+                Action::Exec(command, f) => {
+                    self.execs.push_back((command, f));
+                }
+            }
+        }
+    }
+
+    /// Returns the next process waiting to take over the terminal. These have to run on the
+    /// thread that owns the terminal, so the runtime picks them up itself.
+    pub fn next_exec(&mut self) -> Option<Exec<E>> {
+        self.execs.pop_front()
+    }
+
+    /// The next time something is due, if anything is scheduled at all.
+    pub fn deadline(&self) -> Option<Instant> {
+        let tick = self.tick_rate.map(|rate| self.last_tick + rate);
+        let timer = self.timers.iter().map(|(due, _)| *due).min();
+        match (tick, timer) {
+            (Some(tick), Some(timer)) => Some(tick.min(timer)),
+            (tick, timer) => tick.or(timer),
+        }
+    }
+
+    /// Returns the next event that is ready at `now`, without blocking. Fails if a command
+    /// panicked.
+    pub fn next(&mut self, now: Instant) -> io::Result<Option<Event<E>>> {
+        while let Ok(msg) = self.receiver.try_recv() {
+            self.received.push_back(msg);
+        }
+
+        if let Some(msg) = self.received.pop_front() {
+            self.in_flight -= 1;
+            return match msg {
+                Ok(msg) => Ok(Some(Event::Msg(msg))),
+                Err(message) => Err(io::Error::other(format!("a command panicked: {}", message))),
+            };
+        }
+
+        if let Ok(msg) = self.messages.try_recv() {
+            return Ok(Some(Event::Msg(msg)));
+        }
+
+        if let Some(i) = (0..self.timers.len())
+            .filter(|&i| self.timers[i].0 <= now)
+            .min_by_key(|&i| self.timers[i].0)
+        {
+            return Ok(Some(Event::Msg(self.timers.remove(i).1)));
+        }
+
+        match self.tick_rate {
+            Some(rate) if now >= self.last_tick + rate => {
+                let elapsed = now - self.last_tick;
+                self.last_tick = now;
+                Ok(Some(Event::Tick { now, elapsed }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether commands are still running or timers are pending.
+    pub fn is_busy(&self) -> bool {
+        self.in_flight > 0 || !self.timers.is_empty()
+    }
+
+    /// Blocks until a command finishes or `timeout` expires. Used once there is no more input
+    /// to wait for.
+    pub fn wait(&mut self, timeout: Option<Duration>) {
+        let received = match timeout {
+            Some(timeout) => self.receiver.recv_timeout(timeout),
+            None => self
+                .receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        if let Ok(msg) = received {
+            self.received.push_back(msg);
+        }
+    }
+}
+
+/// What a panic was raised with, when it was a string as it is for `panic!`.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
@@ -0,0 +1,477 @@
+use std::fmt;
+
+/// A color, from the terminal's own palette of sixteen to 24-bit RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// One of the 256 colors of the xterm palette.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+const ANSI_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+    Color::BrightBlack,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightWhite,
+];
+
+/// What xterm shows for the sixteen palette colors by default.
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels of each channel in the 6x6x6 color cube of the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl Color {
+    pub fn normal(self) -> Style {
+        Style::default().fg(self)
+    }
+
+    pub fn bold(self) -> Style {
+        self.normal().bold()
+    }
+
+    pub fn on(self, background: Color) -> Style {
+        self.normal().on(background)
+    }
+
+    /// The position of a palette color among the sixteen.
+    fn ansi_index(self) -> Option<u8> {
+        ANSI_COLORS.iter().position(|&c| c == self).map(|i| i as u8)
+    }
+
+    fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(i) => index_to_rgb(i),
+            color => ANSI_RGB[color.ansi_index().unwrap_or(0) as usize],
+        }
+    }
+
+    /// The closest color the terminal can show.
+    pub fn downgrade(self, depth: ColorDepth) -> Color {
+        match (depth, self) {
+            (ColorDepth::TrueColor, color) => color,
+            (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_index(r, g, b)),
+            (ColorDepth::Ansi256, color) => color,
+            (ColorDepth::Ansi16, Color::Indexed(i)) if i < 16 => ANSI_COLORS[i as usize],
+            (ColorDepth::Ansi16, Color::Indexed(_)) | (ColorDepth::Ansi16, Color::Rgb(..)) => {
+                let (r, g, b) = self.rgb();
+                let nearest = (0..16).min_by_key(|&i| distance(ANSI_RGB[i], (r, g, b)));
+                ANSI_COLORS[nearest.unwrap_or(0)]
+            }
+            (ColorDepth::Ansi16, color) => color,
+        }
+    }
+
+    fn write_sgr(self, f: &mut fmt::Formatter, layer: u8) -> fmt::Result {
+        match (self.ansi_index(), self) {
+            (Some(i), _) if layer == 5 => write!(f, "58;5;{}", i),
+            (Some(i), _) if i < 8 => write!(f, "{}", layer as u16 * 10 + i as u16),
+            (Some(i), _) => write!(f, "{}", (layer as u16 + 6) * 10 + i as u16 - 8),
+            (None, Color::Indexed(i)) => write!(f, "{}8;5;{}", layer, i),
+            (None, Color::Rgb(r, g, b)) => write!(f, "{}8;2;{};{};{}", layer, r, g, b),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+fn index_to_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI_RGB[i as usize],
+        16..=231 => {
+            let i = i - 16;
+            let level = |n: u8| CUBE_LEVELS[n as usize];
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + 10 * (i - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// The closest color of the 6x6x6 cube or the grayscale ramp of the 256 color palette.
+fn rgb_to_index(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    match distance(index_to_rgb(gray), (r, g, b)) < distance(index_to_rgb(cube), (r, g, b)) {
+        true => gray,
+        false => cube,
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Underline {
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+/// Colors and attributes of a cell. Everything is off by default, leaving the terminal's own
+/// colors in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub underline: Option<Underline>,
+    /// Not every terminal can color underlines; the others use the foreground color.
+    pub underline_color: Option<Color>,
+    pub is_bold: bool,
+    pub is_dimmed: bool,
+    pub is_italic: bool,
+    pub is_blink: bool,
+    pub is_reverse: bool,
+    pub is_hidden: bool,
+    pub is_strikethrough: bool,
+    pub is_overlined: bool,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fg(mut self, color: Color) -> Self {
+        self.foreground = Some(color);
+        self
+    }
+
+    pub fn on(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.is_bold = true;
+        self
+    }
+
+    pub fn dimmed(mut self) -> Self {
+        self.is_dimmed = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.is_italic = true;
+        self
+    }
+
+    pub fn underline(self) -> Self {
+        self.underline_style(Underline::Single)
+    }
+
+    pub fn underline_style(mut self, underline: Underline) -> Self {
+        self.underline = Some(underline);
+        self
+    }
+
+    pub fn underline_color(mut self, color: Color) -> Self {
+        self.underline_color = Some(color);
+        self
+    }
+
+    pub fn blink(mut self) -> Self {
+        self.is_blink = true;
+        self
+    }
+
+    pub fn reverse(mut self) -> Self {
+        self.is_reverse = true;
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.is_hidden = true;
+        self
+    }
+
+    pub fn strikethrough(mut self) -> Self {
+        self.is_strikethrough = true;
+        self
+    }
+
+    pub fn overline(mut self) -> Self {
+        self.is_overlined = true;
+        self
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The same style with its colors replaced by the closest ones the terminal can show.
+    pub fn downgrade(self, depth: ColorDepth) -> Self {
+        Self {
+            foreground: self.foreground.map(|c| c.downgrade(depth)),
+            background: self.background.map(|c| c.downgrade(depth)),
+            underline_color: self.underline_color.map(|c| c.downgrade(depth)),
+            ..self
+        }
+    }
+
+    /// The escape sequence that sets this style on a terminal using the default style.
+    pub fn prefix(self) -> Infix {
+        Infix(Style::default(), self)
+    }
+
+    /// The escape sequence that changes this style into `next`, turning on and off only what
+    /// differs between them.
+    pub fn infix(self, next: Style) -> Infix {
+        Infix(self, next)
+    }
+
+    /// The escape sequence that resets the terminal to the default style.
+    pub fn suffix(self) -> &'static str {
+        match self.is_plain() {
+            true => "",
+            false => "\x1b[0m",
+        }
+    }
+}
+
+/// SGR parameters, joined into a single escape sequence.
+struct Codes<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    started: bool,
+}
+
+impl Codes<'_, '_> {
+    fn separate(&mut self) -> fmt::Result {
+        let separator = if self.started { ";" } else { "\x1b[" };
+        self.started = true;
+        self.f.write_str(separator)
+    }
+
+    fn push(&mut self, code: &str) -> fmt::Result {
+        self.separate()?;
+        self.f.write_str(code)
+    }
+
+    fn push_color(&mut self, color: Option<Color>, layer: u8) -> fmt::Result {
+        self.separate()?;
+        match color {
+            Some(color) => color.write_sgr(self.f, layer),
+            None => write!(self.f, "{}9", layer),
+        }
+    }
+
+    fn finish(self) -> fmt::Result {
+        match self.started {
+            true => self.f.write_str("m"),
+            false => Ok(()),
+        }
+    }
+}
+
+/// See `Style::infix`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Infix(Style, Style);
+
+impl fmt::Display for Infix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Infix(mut from, to) = *self;
+        let mut codes = Codes { f, started: false };
+
+        // Bold and dimmed can only be turned off together.
+        if (from.is_bold && !to.is_bold) || (from.is_dimmed && !to.is_dimmed) {
+            codes.push("22")?;
+            from.is_bold = false;
+            from.is_dimmed = false;
+        }
+
+        let attributes = [
+            (from.is_bold, to.is_bold, "1", ""),
+            (from.is_dimmed, to.is_dimmed, "2", ""),
+            (from.is_italic, to.is_italic, "3", "23"),
+            (from.is_blink, to.is_blink, "5", "25"),
+            (from.is_reverse, to.is_reverse, "7", "27"),
+            (from.is_hidden, to.is_hidden, "8", "28"),
+            (from.is_strikethrough, to.is_strikethrough, "9", "29"),
+            (from.is_overlined, to.is_overlined, "53", "55"),
+        ];
+        for &(was, is, on, off) in attributes.iter() {
+            match (was, is) {
+                (false, true) => codes.push(on)?,
+                (true, false) => codes.push(off)?,
+                _ => {}
+            }
+        }
+
+        if from.underline != to.underline {
+            codes.push(match to.underline {
+                None => "24",
+                Some(Underline::Single) => "4",
+                Some(Underline::Double) => "4:2",
+                Some(Underline::Curly) => "4:3",
+                Some(Underline::Dotted) => "4:4",
+                Some(Underline::Dashed) => "4:5",
+            })?;
+        }
+
+        if from.foreground != to.foreground {
+            codes.push_color(to.foreground, 3)?;
+        }
+        if from.background != to.background {
+            codes.push_color(to.background, 4)?;
+        }
+        if from.underline_color != to.underline_color {
+            codes.push_color(to.underline_color, 5)?;
+        }
+
+        codes.finish()
+    }
+}
+
+impl From<ansi_term::Colour> for Color {
+    fn from(color: ansi_term::Colour) -> Self {
+        match color {
+            ansi_term::Colour::Black => Color::Black,
+            ansi_term::Colour::Red => Color::Red,
+            ansi_term::Colour::Green => Color::Green,
+            ansi_term::Colour::Yellow => Color::Yellow,
+            ansi_term::Colour::Blue => Color::Blue,
+            ansi_term::Colour::Purple => Color::Magenta,
+            ansi_term::Colour::Cyan => Color::Cyan,
+            ansi_term::Colour::White => Color::White,
+            ansi_term::Colour::Fixed(i) => Color::Indexed(i),
+            ansi_term::Colour::RGB(r, g, b) => Color::Rgb(r, g, b),
+        }
+    }
+}
+
+impl From<ansi_term::Style> for Style {
+    fn from(style: ansi_term::Style) -> Self {
+        Self {
+            foreground: style.foreground.map(Color::from),
+            background: style.background.map(Color::from),
+            underline: match style.is_underline {
+                true => Some(Underline::Single),
+                false => None,
+            },
+            underline_color: None,
+            is_bold: style.is_bold,
+            is_dimmed: style.is_dimmed,
+            is_italic: style.is_italic,
+            is_blink: style.is_blink,
+            is_reverse: style.is_reverse,
+            is_hidden: style.is_hidden,
+            is_strikethrough: style.is_strikethrough,
+            is_overlined: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::style::{Color, ColorDepth, Style, Underline};
+
+    #[test]
+    fn it_writes_only_what_changed() {
+        let red = Color::Red.bold();
+        let curly = red
+            .underline_style(Underline::Curly)
+            .underline_color(Color::Rgb(1, 2, 3));
+
+        assert_eq!(red.prefix().to_string(), "\x1b[1;31m");
+        assert_eq!(red.infix(curly).to_string(), "\x1b[4:3;58;2;1;2;3m");
+        assert_eq!(curly.infix(red).to_string(), "\x1b[24;59m");
+        assert_eq!(
+            red.infix(Color::BrightBlue.on(Color::Indexed(200)))
+                .to_string(),
+            "\x1b[22;94;48;5;200m"
+        );
+        assert_eq!(red.infix(red).to_string(), "");
+        assert_eq!(
+            Style::from(ansi_term::Colour::Purple.bold()),
+            Color::Magenta.bold()
+        );
+    }
+
+    #[test]
+    fn it_downgrades_colors() {
+        let orange = Color::Rgb(255, 135, 0);
+        assert_eq!(orange.downgrade(ColorDepth::TrueColor), orange);
+        assert_eq!(orange.downgrade(ColorDepth::Ansi256), Color::Indexed(208));
+        assert_eq!(orange.downgrade(ColorDepth::Ansi16), Color::Yellow);
+        assert_eq!(
+            Color::Rgb(128, 128, 128).downgrade(ColorDepth::Ansi256),
+            Color::Indexed(244)
+        );
+        assert_eq!(
+            Color::Indexed(9).downgrade(ColorDepth::Ansi16),
+            Color::BrightRed
+        );
+        assert_eq!(
+            Color::Indexed(21).downgrade(ColorDepth::Ansi16),
+            Color::Blue
+        );
+    }
+}
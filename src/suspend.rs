@@ -0,0 +1,127 @@
+use crate::backend::Waker;
+use crate::event::KeyEvent;
+use crate::sys::{self, SigAction};
+use signal_hook::consts::{SIGCONT, SIGTSTP};
+use signal_hook::iterator::{Handle, Signals};
+use std::io;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Tracks job control: suspend keys, `SIGTSTP` sent by someone else, and `SIGCONT` after the
+/// process was stopped behind our back, e.g. with `SIGSTOP`.
+pub(crate) struct Suspender {
+    keys: Vec<KeyEvent>,
+    stopped: Arc<AtomicBool>,
+    /// How many times `SIGCONT` arrived, and how many of those were dealt with.
+    continued: Arc<AtomicUsize>,
+    resumed: usize,
+    /// Our own suspensions whose `SIGCONT` is still to come.
+    suspensions: usize,
+    signals: Option<Handle>,
+    /// What handled the signals before, to be restored once we are done.
+    previous: Vec<(c_int, SigAction)>,
+}
+
+impl Suspender {
+    pub fn new(keys: &[KeyEvent], waker: Waker) -> io::Result<Self> {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let continued = Arc::new(AtomicUsize::new(0));
+        let previous = [SIGTSTP, SIGCONT]
+            .iter()
+            .map(|&signal| Ok((signal, sys::claim_signal(signal)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut signals = Signals::new([SIGTSTP, SIGCONT])?;
+        let handle = signals.handle();
+
+        let flags = (stopped.clone(), continued.clone());
+        thread::spawn(move || {
+            for signal in signals.forever() {
+                match signal {
+                    SIGTSTP => flags.0.store(true, Ordering::SeqCst),
+                    _ => {
+                        flags.1.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+                waker.wake();
+            }
+        });
+
+        Ok(Self {
+            keys: keys.to_vec(),
+            stopped,
+            continued,
+            resumed: 0,
+            suspensions: 0,
+            signals: Some(handle),
+            previous,
+        })
+    }
+
+    pub fn is_suspend_key(&self, key: &KeyEvent) -> bool {
+        self.keys.iter().any(|binding| key.matches(binding))
+    }
+
+    /// Whether `SIGTSTP` arrived since the last call.
+    pub fn poll_suspend(&mut self) -> bool {
+        self.stopped.swap(false, Ordering::SeqCst)
+    }
+
+    /// Notes that we are about to stop ourselves, so that the `SIGCONT` ending it, whenever it
+    /// arrives, isn't taken for someone else continuing us.
+    pub fn suspending(&mut self) {
+        self.suspensions += 1;
+    }
+
+    /// Whether the process was continued since the last call, other than after suspending.
+    pub fn poll_resume(&mut self) -> bool {
+        let continued = self.continued.load(Ordering::SeqCst);
+        let new = continued - self.resumed;
+        let own = new.min(self.suspensions);
+        self.resumed = continued;
+        self.suspensions -= own;
+        new > own
+    }
+
+    /// Forgets signals that were already dealt with, such as a `SIGTSTP` meant for a child
+    /// process that had the terminal, and the `SIGCONT` that followed it.
+    pub fn discard(&mut self) {
+        self.stopped.store(false, Ordering::SeqCst);
+        self.resumed = self.continued.load(Ordering::SeqCst);
+    }
+}
+
+impl Drop for Suspender {
+    fn drop(&mut self) {
+        if let Some(handle) = self.signals.take() {
+            handle.close();
+        }
+        for (signal, previous) in &self.previous {
+            let _ = sys::release_signal(*signal, previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::Waker;
+    use crate::suspend::Suspender;
+    use crate::testing::SERIAL;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn it_tells_our_own_suspension_from_being_continued() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let mut suspender = Suspender::new(&[], Waker::new(|| {})).unwrap();
+
+        suspender.suspending();
+        assert!(!suspender.poll_resume());
+        // The SIGCONT ending our suspension may only arrive after we drew again.
+        suspender.continued.fetch_add(1, Ordering::SeqCst);
+        assert!(!suspender.poll_resume());
+        suspender.continued.fetch_add(1, Ordering::SeqCst);
+        assert!(suspender.poll_resume());
+        assert!(!suspender.poll_resume());
+    }
+}
@@ -0,0 +1,130 @@
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub type Termios = libc::termios;
+
+fn cvt(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+pub fn get_termios(fd: RawFd) -> io::Result<Termios> {
+    unsafe {
+        let mut termios: Termios = mem::zeroed();
+        cvt(libc::tcgetattr(fd, &mut termios))?;
+        Ok(termios)
+    }
+}
+
+pub fn set_termios(fd: RawFd, termios: &Termios) -> io::Result<()> {
+    cvt(unsafe { libc::tcsetattr(fd, libc::TCSANOW, termios) })?;
+    Ok(())
+}
+
+pub fn is_tty(fd: RawFd) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+/// The size of the terminal behind `fd`, in columns and rows.
+pub fn window_size(fd: RawFd) -> io::Result<(u16, u16)> {
+    unsafe {
+        let mut size: libc::winsize = mem::zeroed();
+        cvt(libc::ioctl(fd, libc::TIOCGWINSZ, &mut size))?;
+        Ok((size.ws_col, size.ws_row))
+    }
+}
+
+pub type SigAction = libc::sigaction;
+
+/// signal-hook installs its handler the first time a signal is registered and never again, so
+/// `release_signal` keeps it here for the next `claim_signal`.
+static HANDLERS: Mutex<Vec<(libc::c_int, SigAction)>> = Mutex::new(Vec::new());
+
+pub fn signal_action(signal: libc::c_int) -> io::Result<SigAction> {
+    unsafe {
+        let mut action: SigAction = mem::zeroed();
+        cvt(libc::sigaction(signal, ptr::null(), &mut action))?;
+        Ok(action)
+    }
+}
+
+/// Installs `action` for `signal` and returns the one it replaced.
+pub fn set_signal_action(signal: libc::c_int, action: &SigAction) -> io::Result<SigAction> {
+    unsafe {
+        let mut previous: SigAction = mem::zeroed();
+        cvt(libc::sigaction(signal, action, &mut previous))?;
+        Ok(previous)
+    }
+}
+
+/// Gets `signal` ready to be registered with signal-hook, and returns what to restore with
+/// `release_signal` once it is unregistered.
+pub fn claim_signal(signal: libc::c_int) -> io::Result<SigAction> {
+    let previous = signal_action(signal)?;
+    let mut handlers = HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(i) = handlers.iter().position(|(s, _)| *s == signal) {
+        set_signal_action(signal, &handlers.remove(i).1)?;
+    }
+    Ok(previous)
+}
+
+/// Restores what handled `signal` before `claim_signal`, which closing a signal-hook handle
+/// doesn't do.
+pub fn release_signal(signal: libc::c_int, previous: &SigAction) -> io::Result<()> {
+    let handler = set_signal_action(signal, previous)?;
+    let mut handlers = HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+    handlers.push((signal, handler));
+    Ok(())
+}
+
+/// Runs `f` with the default action for `signal` in place of whatever handles it now.
+pub fn with_default_action<T, F: FnOnce() -> T>(signal: libc::c_int, f: F) -> io::Result<T> {
+    let mut default: SigAction = unsafe { mem::zeroed() };
+    default.sa_sigaction = libc::SIG_DFL;
+    let previous = set_signal_action(signal, &default)?;
+    let result = f();
+    set_signal_action(signal, &previous)?;
+    Ok(result)
+}
+
+pub fn raw_termios(termios: &Termios) -> Termios {
+    let mut raw = *termios;
+    unsafe { libc::cfmakeraw(&mut raw) };
+    raw
+}
+
+/// Waits until any of `fds` is readable or `timeout` expires, and returns which ones are.
+/// Being interrupted by a signal counts as a timeout.
+pub fn poll_read(fds: &[RawFd], timeout: Option<Duration>) -> io::Result<Vec<bool>> {
+    let mut pollfds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+
+    let timeout = match timeout {
+        Some(timeout) => timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32,
+        None => -1,
+    };
+
+    let result =
+        unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout) };
+    match cvt(result) {
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(vec![false; fds.len()]),
+        Err(e) => Err(e),
+        Ok(_) => Ok(pollfds
+            .iter()
+            .map(|p| p.revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0)
+            .collect()),
+    }
+}
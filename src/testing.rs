@@ -0,0 +1,471 @@
+use crate::backend::Backend;
+use crate::buffer::Buffer;
+use crate::capabilities::Capabilities;
+use crate::event::TerminalEvent;
+use crate::style::Style;
+use crate::types::*;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// A snapshot of the whole screen, as seen by a `TestBackend` when it was flushed.
+pub type Frame = Buffer;
+
+impl Frame {
+    /// Renders the styles of the frame with one legend character per cell, `' '` being the
+    /// default style and `'?'` a style missing from the legend.
+    pub fn style_lines(&self, legend: &[(char, Style)]) -> Vec<String> {
+        self.rows()
+            .map(|row| {
+                row.iter()
+                    .map(
+                        |cell| match legend.iter().find(|(_, s)| s == &cell.style()) {
+                            Some((c, _)) => *c,
+                            None if cell.style() == Style::default() => ' ',
+                            None => '?',
+                        },
+                    )
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[track_caller]
+pub fn assert_text_eq(frame: &Frame, expected: &[&str]) {
+    let actual = frame.lines();
+    if actual != expected {
+        panic!(
+            "frame text mismatch\nexpected:\n{}\nactual:\n{}",
+            expected.join("\n"),
+            actual.join("\n")
+        );
+    }
+}
+
+/// Compares both the text and the styles of a frame. Each character in `styles` refers to a
+/// style in `legend`, `' '` meaning the default style.
+#[track_caller]
+pub fn assert_frame_eq(frame: &Frame, text: &[&str], styles: &[&str], legend: &[(char, Style)]) {
+    assert_text_eq(frame, text);
+    let actual = frame.style_lines(legend);
+    if actual != styles {
+        panic!(
+            "frame style mismatch\nexpected:\n{}\nactual:\n{}",
+            styles.join("\n"),
+            actual.join("\n")
+        );
+    }
+}
+
+/// Signal handling and the panic hook are process-wide, so the tests of this crate that touch
+/// them run one at a time.
+#[cfg(test)]
+pub(crate) static SERIAL: Mutex<()> = Mutex::new(());
+
+enum Step {
+    Input(TerminalEvent),
+    Resize(Size),
+    Wait(Duration),
+}
+
+/// A headless backend that replays scripted inputs, resizes and waits, and records every frame
+/// that changed the screen. Once the script runs out, it reports end of input, which stops the
+/// app.
+pub struct TestBackend {
+    #[cfg(test)]
+    _serial: MutexGuard<'static, ()>,
+    screen: Frame,
+    cursor: Position,
+    dirty: bool,
+    frames: Vec<Frame>,
+    script: VecDeque<Step>,
+    raw_mode: bool,
+    mouse: bool,
+    bracketed_paste: bool,
+    focus_events: bool,
+    alternate_screen: bool,
+    cursor_visible: bool,
+    cursor_shape: Option<(CursorShape, bool)>,
+    suspensions: usize,
+    restorations: Arc<AtomicUsize>,
+    capabilities: Capabilities,
+}
+
+impl TestBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            #[cfg(test)]
+            _serial: SERIAL.lock().unwrap_or_else(|e| e.into_inner()),
+            screen: Frame::new((width, height)),
+            cursor: (1, 1),
+            dirty: false,
+            frames: Vec::new(),
+            script: VecDeque::new(),
+            raw_mode: false,
+            mouse: false,
+            bracketed_paste: false,
+            focus_events: false,
+            alternate_screen: false,
+            cursor_visible: true,
+            cursor_shape: None,
+            suspensions: 0,
+            restorations: Arc::new(AtomicUsize::new(0)),
+            capabilities: Capabilities::default(),
+        }
+    }
+
+    pub fn with_inputs<T: Into<TerminalEvent>, I: IntoIterator<Item = T>>(
+        mut self,
+        inputs: I,
+    ) -> Self {
+        self.script
+            .extend(inputs.into_iter().map(|input| Step::Input(input.into())));
+        self
+    }
+
+    /// Where the cursor is when the app starts, which matters to inline viewports.
+    pub fn with_cursor(mut self, position: Position) -> Self {
+        self.cursor = position;
+        self
+    }
+
+    /// What the app is told about the terminal. Frames always record styles as they are.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn push_input<T: Into<TerminalEvent>>(&mut self, input: T) {
+        self.script.push_back(Step::Input(input.into()));
+    }
+
+    pub fn push_resize(&mut self, width: u16, height: u16) {
+        self.script.push_back(Step::Resize((width, height)));
+    }
+
+    /// Keeps the app waiting for input for `duration`, e.g. so that ticks and timers are due.
+    pub fn push_wait(&mut self, duration: Duration) {
+        self.script.push_back(Step::Wait(duration));
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// The last flushed frame.
+    pub fn frame(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+
+    pub fn is_raw_mode(&self) -> bool {
+        self.raw_mode
+    }
+
+    pub fn is_mouse_enabled(&self) -> bool {
+        self.mouse
+    }
+
+    pub fn is_bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    pub fn is_focus_events(&self) -> bool {
+        self.focus_events
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.alternate_screen
+    }
+
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// The shape and blinking set by the app, or `None` if it is the user's own.
+    pub fn cursor_shape(&self) -> Option<(CursorShape, bool)> {
+        self.cursor_shape
+    }
+
+    /// How many times the app suspended itself.
+    pub fn suspensions(&self) -> usize {
+        self.suspensions
+    }
+
+    /// Counts the calls of the restorer handed out to the panic hook. The counter is shared,
+    /// so that it can be read while the app is running.
+    pub fn restorations(&self) -> Arc<AtomicUsize> {
+        self.restorations.clone()
+    }
+}
+
+impl Backend for TestBackend {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        self.raw_mode = true;
+        Ok(())
+    }
+
+    fn leave_raw_mode(&mut self) -> io::Result<()> {
+        self.raw_mode = false;
+        Ok(())
+    }
+
+    fn enable_mouse(&mut self) -> io::Result<()> {
+        self.mouse = true;
+        Ok(())
+    }
+
+    fn disable_mouse(&mut self) -> io::Result<()> {
+        self.mouse = false;
+        Ok(())
+    }
+
+    fn enable_bracketed_paste(&mut self) -> io::Result<()> {
+        self.bracketed_paste = true;
+        Ok(())
+    }
+
+    fn disable_bracketed_paste(&mut self) -> io::Result<()> {
+        self.bracketed_paste = false;
+        Ok(())
+    }
+
+    fn enable_focus_events(&mut self) -> io::Result<()> {
+        self.focus_events = true;
+        Ok(())
+    }
+
+    fn disable_focus_events(&mut self) -> io::Result<()> {
+        self.focus_events = false;
+        Ok(())
+    }
+
+    fn enter_alternate_screen(&mut self) -> io::Result<()> {
+        self.alternate_screen = true;
+        Ok(())
+    }
+
+    fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        self.alternate_screen = false;
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.cursor_visible = false;
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.cursor_visible = true;
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape, blinking: bool) -> io::Result<()> {
+        self.cursor_shape = Some((shape, blinking));
+        Ok(())
+    }
+
+    fn reset_cursor_shape(&mut self) -> io::Result<()> {
+        self.cursor_shape = None;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.screen = Frame::new(self.screen.size());
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn goto(&mut self, position: Position) -> io::Result<()> {
+        self.cursor = position;
+        Ok(())
+    }
+
+    fn clear_from(&mut self, row: u16) -> io::Result<()> {
+        let (w, h) = self.screen.size();
+        for y in row..=h {
+            for x in 1..=w {
+                self.screen.set(&(x, y), Cell::default());
+            }
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn scroll_up(&mut self, lines: u16) -> io::Result<()> {
+        if lines > 0 {
+            self.screen.scroll_up(lines);
+            self.cursor = (1, self.screen.size().1);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn cursor_position(&mut self) -> io::Result<Option<Position>> {
+        Ok(Some(self.cursor))
+    }
+
+    fn write_cell(&mut self, cell: &Cell) -> io::Result<()> {
+        let (x, y) = self.cursor;
+        self.screen.set(&(x, y), cell.clone());
+        self.cursor = (x.saturating_add(cell.width().max(1)), y);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Frames also record the cursor while the app shows it on the alternate screen.
+    fn flush(&mut self) -> io::Result<()> {
+        self.screen.hide_cursor();
+        if self.cursor_visible && self.alternate_screen {
+            let (shape, blinking) = self.cursor_shape.unwrap_or((CursorShape::Block, true));
+            self.screen.show_cursor(Cursor {
+                position: self.cursor,
+                shape,
+                blinking,
+            });
+        }
+        let moved = self
+            .frames
+            .last()
+            .is_some_and(|last| last.cursor() != self.screen.cursor());
+        if (self.dirty || moved) && self.frames.last() != Some(&self.screen) {
+            self.frames.push(self.screen.clone());
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn poll_input(&mut self, timeout: Option<Duration>) -> io::Result<Option<TerminalEvent>> {
+        match self.script.pop_front() {
+            Some(Step::Input(input)) => Ok(Some(input)),
+            Some(Step::Wait(duration)) => {
+                let waited = timeout.map_or(duration, |timeout| timeout.min(duration));
+                thread::sleep(waited);
+                if waited < duration {
+                    self.script.push_front(Step::Wait(duration - waited));
+                }
+                Ok(None)
+            }
+            Some(step) => {
+                self.script.push_front(step);
+                Ok(None)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "no more scripted inputs",
+            )),
+        }
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        Ok(self.screen.size())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn poll_resize(&mut self) -> io::Result<Option<Size>> {
+        match self.script.pop_front() {
+            Some(Step::Resize(size)) => {
+                self.screen = Frame::new(size);
+                Ok(Some(size))
+            }
+            Some(step) => {
+                self.script.push_front(step);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        self.suspensions += 1;
+        Ok(())
+    }
+
+    fn restorer(&self) -> Box<dyn Fn() + Send + Sync> {
+        let restorations = self.restorations.clone();
+        Box::new(move || {
+            restorations.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::app;
+    use crate::border::simple_border;
+    use crate::canvas::Canvas;
+    use crate::event::{Event, Input, KeyCode, KeyEvent};
+    use crate::style::{Color, Style};
+    use crate::testing::{assert_frame_eq, assert_text_eq, TestBackend};
+    use crate::types::*;
+    use std::io;
+
+    struct MyCanvas {}
+
+    impl Canvas for MyCanvas {
+        fn top_left_corner(&self) -> io::Result<Position> {
+            Ok((1, 1))
+        }
+        fn bottom_right_corner(&self) -> io::Result<Position> {
+            Ok((4, 3))
+        }
+    }
+
+    fn init(_: &Context<()>) -> io::Result<(MyCanvas, char)> {
+        Ok((MyCanvas {}, 'a'))
+    }
+
+    fn view(canvas: &MyCanvas, model: &char, position: &Position) -> io::Result<Option<TermPixel>> {
+        match simple_border(canvas, model, position)? {
+            Some(tp) => Ok(Some(tp)),
+            None if position == &(2, 2) => Ok(Some((*model, Style::default().fg(Color::Red)))),
+            None => Ok(Some(('.', Style::default()))),
+        }
+    }
+
+    fn update(_: &MyCanvas, model: &mut char, event: &Event<()>) -> io::Result<Event<()>> {
+        if let Event::Input(Input::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            ..
+        })) = event
+        {
+            *model = *c;
+        }
+        Ok(Event::NoOp)
+    }
+
+    #[test]
+    fn it_records_frames() {
+        let mut backend =
+            TestBackend::new(5, 4).with_inputs(vec![Input::Key(KeyCode::Char('b').into())]);
+        app::run_with_backend(
+            &mut backend,
+            &init,
+            &view,
+            &update,
+            &app::Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(backend.frames().len(), 2);
+        assert_text_eq(&backend.frames()[0], &["┌──┐ ", "│a.│ ", "└──┘ ", "     "]);
+        assert_frame_eq(
+            backend.frame().unwrap(),
+            &["┌──┐ ", "│b.│ ", "└──┘ ", "     "],
+            &["     ", " r   ", "     ", "     "],
+            &[('r', Style::default().fg(Color::Red))],
+        );
+        assert!(!backend.is_raw_mode());
+        assert!(!backend.is_alternate_screen());
+        assert!(backend.is_cursor_visible());
+    }
+}
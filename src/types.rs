@@ -1,21 +1,26 @@
+pub use crate::buffer::{Buffer, Cell};
 pub use crate::canvas::Canvas;
+pub use crate::capabilities::Capabilities;
+pub use crate::cmd::Cmd;
+pub use crate::context::{AppHandle, Context};
+pub use crate::cursor::{Cursor, CursorShape};
 pub use crate::event::Event;
-use ansi_term::Style;
+use crate::style::Style;
 use std::io;
 
 pub type Position = (u16, u16); // x, y
 pub type Size = (u16, u16); // width, height
-pub type TermPixel = (char, Style);
+pub type TermPixel = (char, Style); // see Cell for grapheme clusters
 
 pub trait Model: Sized {}
 impl<T: Sized> Model for T {}
 
-pub trait Init<C: Canvas, M: Model>: Fn() -> io::Result<(C, M)> {}
-impl<T, C, M> Init<C, M> for T
+pub trait Init<C: Canvas, M: Model, E>: Fn(&Context<E>) -> io::Result<(C, M)> {}
+impl<T, C, M, E> Init<C, M, E> for T
 where
     C: Canvas,
     M: Model,
-    T: Fn() -> io::Result<(C, M)>,
+    T: Fn(&Context<E>) -> io::Result<(C, M)>,
 {
 }
 
@@ -32,14 +37,69 @@ where
 {
 }
 
-pub trait Update<C: Canvas, M: Model, E>:
-    Fn(&C, &mut M, &Event<E>) -> io::Result<Event<E>>
+/// Paints a whole frame at once. Closures taking `&mut Buffer` draw directly, and per-position
+/// views are rendered one position at a time. `K` tells the two apart and is always inferred.
+pub trait Draw<C: Canvas, M: Model, K = Immediate> {
+    fn draw(&self, canvas: &C, model: &M, buffer: &mut Buffer) -> io::Result<()>;
+}
+
+pub struct Immediate;
+pub struct PerPosition;
+
+impl<T, C, M> Draw<C, M, Immediate> for T
+where
+    C: Canvas,
+    M: Model,
+    T: Fn(&C, &M, &mut Buffer) -> io::Result<()>,
+{
+    fn draw(&self, canvas: &C, model: &M, buffer: &mut Buffer) -> io::Result<()> {
+        self(canvas, model, buffer)
+    }
+}
+
+impl<T, C, M> Draw<C, M, PerPosition> for T
+where
+    C: Canvas,
+    M: Model,
+    T: View<C, M>,
+{
+    fn draw(&self, canvas: &C, model: &M, buffer: &mut Buffer) -> io::Result<()> {
+        crate::app::render(canvas, model, self, buffer)
+    }
+}
+
+/// What an update can return: the next event, a command, or both.
+pub trait Transition<E> {
+    fn split(self) -> (Event<E>, Cmd<E>);
+}
+
+impl<E> Transition<E> for Event<E> {
+    fn split(self) -> (Event<E>, Cmd<E>) {
+        (self, Cmd::none())
+    }
+}
+
+impl<E> Transition<E> for Cmd<E> {
+    fn split(self) -> (Event<E>, Cmd<E>) {
+        (Event::NoOp, self)
+    }
+}
+
+impl<E> Transition<E> for (Event<E>, Cmd<E>) {
+    fn split(self) -> (Event<E>, Cmd<E>) {
+        self
+    }
+}
+
+pub trait Update<C: Canvas, M: Model, E, R: Transition<E>>:
+    Fn(&C, &mut M, &Event<E>) -> io::Result<R>
 {
 }
-impl<T, C, M, E> Update<C, M, E> for T
+impl<T, C, M, E, R> Update<C, M, E, R> for T
 where
     C: Canvas,
     M: Model,
-    T: Fn(&C, &mut M, &Event<E>) -> io::Result<Event<E>>,
+    R: Transition<E>,
+    T: Fn(&C, &mut M, &Event<E>) -> io::Result<R>,
 {
 }